@@ -10,6 +10,7 @@ const QUERY: usize = 4_000_000;
 const BOXSIZE: [T; D] = [1.0; D];
 const NDATA: usize = 4_000_000;
 const LS: usize = 32;
+const SELF_JOIN_NDATA: usize = 1_000_000;
 
 fn criterion_benchmark(c: &mut Criterion) {
     let data: Vec<[T; D]> = (0..NDATA)
@@ -207,6 +208,42 @@ fn criterion_benchmark(c: &mut Criterion) {
         //     })
         // });
     }
+    group.finish();
+
+    // Self join, i.e. the k nearest neighbors of every point in the tree
+    let data: Vec<[T; D]> = (0..SELF_JOIN_NDATA)
+        .map(|_| [(); D].map(|_| rand::random()))
+        .collect();
+    let mut group = c.benchmark_group(format!(
+        "self join (ndata = {})",
+        SELF_JOIN_NDATA.to_formatted_string(&Locale::en)
+    ));
+    group.sample_size(10).confidence_level(0.95);
+
+    for k in [10] {
+        let tree = Tree::new(black_box(&data), LS).unwrap();
+
+        group.bench_function(format!("k={k} parallel nonpbc"), |b| {
+            b.iter(|| {
+                tree.query_nearest_k_parallel(black_box(tree.get_data()), black_box(k))
+                    .unwrap()
+            })
+        });
+        group.bench_function(format!("k={k} all nonpbc"), |b| {
+            b.iter(|| tree.all_nearest_k(black_box(k), false).unwrap())
+        });
+
+        let tree = tree.with_boxsize(&BOXSIZE).unwrap();
+        group.bench_function(format!("k={k} parallel pbc"), |b| {
+            b.iter(|| {
+                tree.query_nearest_k_parallel(black_box(tree.get_data()), black_box(k))
+                    .unwrap()
+            })
+        });
+        group.bench_function(format!("k={k} all pbc"), |b| {
+            b.iter(|| tree.all_nearest_k(black_box(k), false).unwrap())
+        });
+    }
 }
 
 criterion_group!(benches, criterion_benchmark);
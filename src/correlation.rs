@@ -0,0 +1,158 @@
+use std::fmt::Debug;
+
+use crate::{
+    metric::Metric,
+    point::Float,
+    utils::{check_radius_return, FnntwError, FnntwResult},
+    Tree,
+};
+
+/// Estimators of the two-point correlation function `xi(r)`, in terms of the data-data (`DD`),
+/// data-random (`DR`) and random-random (`RR`) pair counts normalized by the number of pairs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Estimator {
+    /// `DD / RR - 1`
+    Natural,
+    /// `DD / DR - 1`
+    DavisPeebles,
+    /// `DD * RR / DR^2 - 1`
+    Hamilton,
+    /// `(DD - 2 DR + RR) / RR`
+    LandySzalay,
+}
+
+impl Estimator {
+    /// Computes `xi` from the normalized pair counts of a single bin. Counts that are
+    /// not used by the estimator are ignored.
+    pub fn estimate<T: Float>(&self, dd: T, dr: T, rr: T) -> T {
+        match self {
+            Estimator::Natural => dd / rr - T::one(),
+            Estimator::DavisPeebles => dd / dr - T::one(),
+            Estimator::Hamilton => dd * rr / dr.powi(2) - T::one(),
+            Estimator::LandySzalay => (dd - (dr + dr) + rr) / rr,
+        }
+    }
+
+    fn uses_dr(&self) -> bool {
+        !matches!(self, Estimator::Natural)
+    }
+
+    fn uses_rr(&self) -> bool {
+        !matches!(self, Estimator::DavisPeebles)
+    }
+}
+
+impl<'t, T: Float + Debug, const D: usize, M: Metric<T, D>> Tree<'t, T, D, M> {
+    /// Computes the two-point correlation function of the data in this tree in the radial bins
+    /// defined by `bin_edges`, using the catalog of `randoms` and the given `estimator`. The value
+    /// at index `i` of the result corresponds to separations `bin_edges[i] <= r < bin_edges[i + 1]`.
+    ///
+    /// If the trees have a boxsize (which must be the same for both), the minimum image
+    /// separation is used. The data, and the randoms if the estimator uses the random-random
+    /// pairs, must contain at least two points.
+    pub fn correlation_function(
+        &self,
+        randoms: &Tree<'_, T, D, M>,
+        bin_edges: &[T],
+        estimator: Estimator,
+    ) -> FnntwResult<Vec<T>, T> {
+        let num_bins = bin_edges.len().saturating_sub(1);
+
+        let dd = self.normalized_auto_pairs(bin_edges)?;
+        let dr = if estimator.uses_dr() {
+            let pairs = T::from(self.input.len()).unwrap() * T::from(randoms.input.len()).unwrap();
+            self.count_pairs(randoms, bin_edges)?
+                .into_iter()
+                .map(|count| T::from(count).unwrap() / pairs)
+                .collect()
+        } else {
+            vec![T::zero(); num_bins]
+        };
+        let rr = if estimator.uses_rr() {
+            randoms.normalized_auto_pairs(bin_edges)?
+        } else {
+            vec![T::zero(); num_bins]
+        };
+
+        Ok(dd
+            .into_iter()
+            .zip(dr)
+            .zip(rr)
+            .map(|((dd, dr), rr)| estimator.estimate(dd, dr, rr))
+            .collect())
+    }
+
+    /// Counts the pairs of distinct points in this tree, normalized by the number of such pairs.
+    /// There must be at least two points, since otherwise there are no such pairs.
+    fn normalized_auto_pairs(&self, bin_edges: &[T]) -> FnntwResult<Vec<T>, T> {
+        if self.input.len() < 2 {
+            return Err(FnntwError::TooFewPoints);
+        }
+        let mut counts = self.count_pairs(self, bin_edges)?;
+
+        // Remove every point's pair with itself
+        if bin_edges[0] == T::zero() {
+            counts[0] -= self.input.len() as u64;
+        }
+
+        let num = T::from(self.input.len()).unwrap();
+        let pairs = num * (num - T::one());
+        Ok(counts
+            .into_iter()
+            .map(|count| T::from(count).unwrap() / pairs)
+            .collect())
+    }
+}
+
+impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
+    /// Computes the two-point correlation function of the data in this tree in the radial bins
+    /// defined by `bin_edges`, using the analytic random-random pair counts of the periodic box.
+    /// The tree must be periodic along every axis, and `bin_edges` must be smaller than half of
+    /// the boxsize.
+    ///
+    /// For a periodic box all of the [`Estimator`]s reduce to [`Estimator::Natural`], since the
+    /// analytic data-random and random-random counts are equal.
+    pub fn correlation_function_periodic(&self, bin_edges: &[T]) -> FnntwResult<Vec<T>, T> {
+        let Some(boxsize) = self
+            .boxsize
+            .as_ref()
+            .filter(|b| b.iter().all(|s| s.is_finite()))
+        else {
+            return Err(FnntwError::MissingBoxsize);
+        };
+        if let Some(largest_edge) = bin_edges.last() {
+            check_radius_return(*largest_edge, Some(boxsize), &self.metric)?;
+        }
+
+        let dd = self.normalized_auto_pairs(bin_edges)?;
+
+        // Fraction of the box occupied by each shell
+        let volume = boxsize.iter().fold(T::one(), |acc, side| acc * **side);
+        let unit_ball_volume = unit_ball_volume::<T>(D);
+        let rr = bin_edges.windows(2).map(|edges| {
+            unit_ball_volume * (edges[1].powi(D as i32) - edges[0].powi(D as i32)) / volume
+        });
+
+        Ok(dd
+            .into_iter()
+            .zip(rr)
+            .map(|(dd, rr)| Estimator::Natural.estimate(dd, T::zero(), rr))
+            .collect())
+    }
+}
+
+/// Volume of the unit ball in `d` dimensions
+fn unit_ball_volume<T: Float>(d: usize) -> T {
+    // V_0 = 1, V_1 = 2, and V_d = V_{d-2} * 2 pi / d
+    let two_pi = T::from(2.0 * std::f64::consts::PI).unwrap();
+    let parity = d % 2;
+    let mut volume = if parity == 1 {
+        T::from(2.0).unwrap()
+    } else {
+        T::one()
+    };
+    for dim in ((2 + parity)..=d).step_by(2) {
+        volume = volume * two_pi / T::from(dim).unwrap();
+    }
+    volume
+}
@@ -0,0 +1,117 @@
+use std::fmt::Debug;
+
+use crate::{
+    owned::OwnedTree,
+    point::Float,
+    utils::{check_point, FnntwError, FnntwResult},
+    Tree,
+};
+
+/// Returns `point` scaled to unit (euclidean) norm, or an error if its norm is zero.
+pub fn normalize<T: Float + Debug, const D: usize>(point: &[T; D]) -> FnntwResult<[T; D], T> {
+    check_point(point)?;
+
+    // Scale by the largest component first so that squaring neither overflows nor underflows.
+    // The scaled components are at most one, so their norm is finite and at least one.
+    let max = point.iter().fold(T::zero(), |max, x| max.max(x.abs()));
+    if max == T::zero() {
+        return Err(FnntwError::ZeroNormVector);
+    }
+    let scaled = point.map(|x| x / max);
+    let norm = scaled
+        .iter()
+        .fold(T::zero(), |acc, x| acc + x.powi(2))
+        .sqrt();
+    Ok(scaled.map(|x| x / norm))
+}
+
+/// Returns the cosine similarity of the unit vectors `a` and `b`, i.e. their dot product.
+fn similarity<T: Float, const D: usize>(a: &[T; D], b: &[T; D]) -> T {
+    a.iter()
+        .zip(b)
+        .fold(T::zero(), |acc, (a, b)| acc + *a * *b)
+        .max(-T::one())
+        .min(T::one())
+}
+
+/// A kdtree for nearest neighbor queries by cosine similarity. The tree is built on the
+/// normalized data, for which the most similar points are the nearest neighbors in
+/// euclidean distance, and indices refer to the original data.
+///
+/// Queries return the cosine similarities of the neighbors (in descending order) rather than
+/// distances, regardless of the `sqrt-dist2` feature. Rows of the data and queries with zero
+/// norm are rejected with [`FnntwError::ZeroNormVector`].
+pub struct CosineTree<'t, T: Float, const D: usize> {
+    /// Data used to build the tree
+    input: &'t [[T; D]],
+
+    /// Tree built on (and owning) the normalized data
+    tree: OwnedTree<'t, T, D>,
+}
+
+impl<'t, T: Float + Debug, const D: usize> CosineTree<'t, T, D> {
+    /// Create a new [CosineTree] using a nonparallel build.
+    pub fn new(input: &'t [[T; D]], leafsize: usize) -> FnntwResult<CosineTree<'t, T, D>, T> {
+        let normalized = input
+            .iter()
+            .map(normalize)
+            .collect::<FnntwResult<Vec<_>, T>>()?;
+        let tree = OwnedTree::new(normalized, leafsize)?;
+
+        Ok(CosineTree { input, tree })
+    }
+
+    /// Create a new [CosineTree] using a parallel build. See [`Tree::new_parallel`].
+    pub fn new_parallel(
+        input: &'t [[T; D]],
+        leafsize: usize,
+        par_split_level: usize,
+    ) -> FnntwResult<CosineTree<'t, T, D>, T> {
+        let normalized = input
+            .iter()
+            .map(normalize)
+            .collect::<FnntwResult<Vec<_>, T>>()?;
+        let tree = OwnedTree::new_parallel(normalized, leafsize, par_split_level)?;
+
+        Ok(CosineTree { input, tree })
+    }
+
+    /// Returns the data used to build the tree, as given (i.e. not normalized).
+    pub fn get_data(&self) -> &[[T; D]] {
+        self.input
+    }
+
+    /// Returns the euclidean tree built on the normalized data, e.g. for queries not provided by
+    /// [CosineTree]. Its indices refer to the original data, but queries must first be
+    /// normalized with [`normalize`].
+    pub fn normalized_tree(&self) -> &Tree<'_, T, D> {
+        self.tree.tree()
+    }
+
+    /// Finds the most similar point to the `query`, returning its cosine similarity and index.
+    pub fn query_nearest(&self, query: &[T; D]) -> FnntwResult<(T, u64), T> {
+        let query = normalize(query)?;
+        let index = self.tree.tree().query_nearest(&query)?.1;
+
+        Ok((self.similarity(&query, index), index))
+    }
+
+    /// Finds the `k` most similar points to the `query`, returning their cosine similarities
+    /// (in descending order) and indices.
+    pub fn query_nearest_k(&self, query: &[T; D], k: usize) -> FnntwResult<(Vec<T>, Vec<u64>), T> {
+        let query = normalize(query)?;
+        let indices = self.tree.tree().query_nearest_k(&query, k)?.1;
+
+        let similarities = indices
+            .iter()
+            .map(|index| self.similarity(&query, *index))
+            .collect();
+        Ok((similarities, indices))
+    }
+
+    /// Returns the cosine similarity between the normalized `query` and the point with the
+    /// given `index`.
+    fn similarity(&self, query: &[T; D], index: u64) -> T {
+        similarity(query, &self.tree.data()[index as usize])
+    }
+}
@@ -3,6 +3,7 @@ use std::ops::AddAssign;
 use ordered_float::NotNan;
 
 use crate::{
+    metric::Metric,
     point::{Float, Point},
     query_k::container::Container,
 };
@@ -55,7 +56,8 @@ where
     (ax + nonax, ax, nonax)
 }
 
-pub fn new_best<'t, 'i, 'o, T: Float, const D: usize>(
+pub fn new_best<'t, 'i, 'o, T: Float, const D: usize, M: Metric<T, D>>(
+    metric: &M,
     query: &[NotNan<T>; D],
     candidate: &'i Point<T, D>,
     current_best_dist_sq: &'o mut T,
@@ -69,8 +71,8 @@ pub fn new_best<'t, 'i, 'o, T: Float, const D: usize>(
         "distance must be nonnegative"
     );
 
-    // Run usual squared_euclidean fn
-    let dist_sq: T = squared_euclidean(query, candidate.position());
+    // Run the metric's (reduced) distance fn
+    let dist_sq: T = metric.dist(query, candidate.position());
 
     // Compare squared dist
     if dist_sq < *current_best_dist_sq {
@@ -80,7 +82,8 @@ pub fn new_best<'t, 'i, 'o, T: Float, const D: usize>(
     }
 }
 
-pub(crate) fn new_best_kth<'t, 'i, 'o, T: Float, const D: usize>(
+pub(crate) fn new_best_kth<'t, 'i, 'o, T: Float, const D: usize, M: Metric<T, D>>(
+    metric: &M,
     query: &[NotNan<T>; D],
     candidate: &'i Point<T, D>,
     container: &'o mut Container<'i, T, D>,
@@ -88,17 +91,18 @@ pub(crate) fn new_best_kth<'t, 'i, 'o, T: Float, const D: usize>(
     'i: 'o,
     't: 'i,
 {
-    // Run usual squared_euclidean fn
-    let dist_sq: T = squared_euclidean(query, candidate.position());
+    // Run the metric's (reduced) distance fn
+    let dist_sq: T = metric.dist(query, candidate.position());
 
     // Compare squared dist
-    if dist_sq <= *container.best_dist2() {
+    if dist_sq <= *container.best_dist2() && !container.excludes(candidate) {
         container.push((dist_sq, candidate));
     }
 }
 
 #[cfg(all(feature = "parallel", feature = "no-position"))] // TODO: separate axis from parallel
-pub(crate) fn new_best_kth_axis<'t, 'i, 'o, T: Float, const D: usize>(
+pub(crate) fn new_best_kth_axis<'t, 'i, 'o, T: Float, const D: usize, M: Metric<T, D>>(
+    metric: &M,
     query: &[NotNan<T>; D],
     candidate: &'i Point<T, D>,
     container: &'o mut ContainerAxis<'i, T, D>,
@@ -107,8 +111,8 @@ pub(crate) fn new_best_kth_axis<'t, 'i, 'o, T: Float, const D: usize>(
     'i: 'o,
     't: 'i,
 {
-    // Run usual squared_euclidean fn
-    let (dist2, ax, nonax) = squared_euclidean_axis(query, candidate.position(), axis);
+    // Run the metric's (reduced) distance fn, split into the axis and the other axes
+    let (dist2, ax, nonax) = metric.dist_axis(query, candidate.position(), axis);
 
     // Compare squared dist
     if dist2 <= *container.best_dist2() {
@@ -145,6 +149,240 @@ pub fn calc_dist_sq_to_space<T: Float, const D: usize>(
     squared_euclidean(query, &closest_point)
 }
 
+/// Calculate the largest distance from `query` to some space defined by `lower` and `upper`.
+///
+/// This is the squared euclidean distance between `query` and the corner of the space
+/// farthest from it, so every point in the space is no farther than this from `query`.
+pub fn calc_max_dist_sq_to_space<T: Float, const D: usize>(
+    query: &[NotNan<T>; D],
+    lower: &[NotNan<T>; D],
+    upper: &[NotNan<T>; D],
+) -> T {
+    // Initialize accumulator var
+    let mut dist_sq: T = T::zero();
+
+    for i in 0..D {
+        // safety: made safe by const generic
+        unsafe {
+            let query_component = query.get_unchecked(i);
+            let to_lower = (query_component - lower.get_unchecked(i)).abs();
+            let to_upper = (query_component - upper.get_unchecked(i)).abs();
+            dist_sq += to_lower.max(to_upper).powi(2);
+        }
+    }
+
+    dist_sq
+}
+
+/// Calculate the squared minimum image distance between `a` and `b` in a periodic box of
+/// size `boxsize`. Both points must lie within the box.
+pub fn squared_euclidean_periodic<T: Float, const D: usize>(
+    a: &[NotNan<T>; D],
+    b: &[NotNan<T>; D],
+    boxsize: &[NotNan<T>; D],
+) -> T {
+    // Initialize accumulator var
+    let mut dist_sq: T = T::zero();
+
+    for idx in 0..D {
+        // safety: made safe by const generic
+        unsafe {
+            let dx = (a.get_unchecked(idx) - b.get_unchecked(idx)).abs();
+            dist_sq += dx.min(**boxsize.get_unchecked(idx) - dx).powi(2);
+        }
+    }
+
+    dist_sq
+}
+
+/// Calculate the weighted squared euclidean distance `sum_i weights[i] * dx_i^2` between `a`
+/// and `b`.
+pub fn weighted_squared_euclidean<T: Float, const D: usize>(
+    a: &[NotNan<T>; D],
+    b: &[NotNan<T>; D],
+    weights: &[T; D],
+) -> T {
+    // Initialize accumulator var
+    let mut dist_sq: T = T::zero();
+
+    for idx in 0..D {
+        // safety: made safe by const generic
+        unsafe {
+            dist_sq +=
+                *weights.get_unchecked(idx) * (a.get_unchecked(idx) - b.get_unchecked(idx)).powi(2);
+        }
+    }
+
+    dist_sq
+}
+
+/// Calculate the weighted squared minimum image distance between `a` and `b` in a periodic box
+/// of size `boxsize`. Both points must lie within the box.
+pub fn weighted_squared_euclidean_periodic<T: Float, const D: usize>(
+    a: &[NotNan<T>; D],
+    b: &[NotNan<T>; D],
+    boxsize: &[NotNan<T>; D],
+    weights: &[T; D],
+) -> T {
+    // Initialize accumulator var
+    let mut dist_sq: T = T::zero();
+
+    for idx in 0..D {
+        // safety: made safe by const generic
+        unsafe {
+            let dx = (a.get_unchecked(idx) - b.get_unchecked(idx)).abs();
+            dist_sq +=
+                *weights.get_unchecked(idx) * dx.min(**boxsize.get_unchecked(idx) - dx).powi(2);
+        }
+    }
+
+    dist_sq
+}
+
+/// Calculate the manhattan (L1) distance between `a` and `b`.
+pub fn manhattan<T: Float, const D: usize>(a: &[NotNan<T>; D], b: &[NotNan<T>; D]) -> T {
+    // Initialize accumulator var
+    let mut dist: T = T::zero();
+
+    for idx in 0..D {
+        // safety: made safe by const generic
+        unsafe {
+            dist += (a.get_unchecked(idx) - b.get_unchecked(idx)).abs();
+        }
+    }
+
+    dist
+}
+
+/// Calculate the manhattan (L1) minimum image distance between `a` and `b` in a periodic box
+/// of size `boxsize`. Both points must lie within the box.
+pub fn manhattan_periodic<T: Float, const D: usize>(
+    a: &[NotNan<T>; D],
+    b: &[NotNan<T>; D],
+    boxsize: &[NotNan<T>; D],
+) -> T {
+    // Initialize accumulator var
+    let mut dist: T = T::zero();
+
+    for idx in 0..D {
+        // safety: made safe by const generic
+        unsafe {
+            let dx = (a.get_unchecked(idx) - b.get_unchecked(idx)).abs();
+            dist += dx.min(**boxsize.get_unchecked(idx) - dx);
+        }
+    }
+
+    dist
+}
+
+/// Calculate the chebyshev (L-infinity) distance between `a` and `b`.
+pub fn chebyshev<T: Float, const D: usize>(a: &[NotNan<T>; D], b: &[NotNan<T>; D]) -> T {
+    // Initialize accumulator var
+    let mut dist: T = T::zero();
+
+    for idx in 0..D {
+        // safety: made safe by const generic
+        unsafe {
+            dist = dist.max((a.get_unchecked(idx) - b.get_unchecked(idx)).abs());
+        }
+    }
+
+    dist
+}
+
+/// Calculate the chebyshev (L-infinity) minimum image distance between `a` and `b` in a
+/// periodic box of size `boxsize`. Both points must lie within the box.
+pub fn chebyshev_periodic<T: Float, const D: usize>(
+    a: &[NotNan<T>; D],
+    b: &[NotNan<T>; D],
+    boxsize: &[NotNan<T>; D],
+) -> T {
+    // Initialize accumulator var
+    let mut dist: T = T::zero();
+
+    for idx in 0..D {
+        // safety: made safe by const generic
+        unsafe {
+            let dx = (a.get_unchecked(idx) - b.get_unchecked(idx)).abs();
+            dist = dist.max(dx.min(**boxsize.get_unchecked(idx) - dx));
+        }
+    }
+
+    dist
+}
+
+/// Calculate the smallest and largest squared distances between any point in the space defined
+/// by `lower_a` and `upper_a` and any point in the space defined by `lower_b` and `upper_b`.
+///
+/// If a `boxsize` is given, minimum image distances are used, in which case both spaces must
+/// lie within the box.
+pub fn calc_dist_sq_between_spaces<T: Float, const D: usize>(
+    lower_a: &[NotNan<T>; D],
+    upper_a: &[NotNan<T>; D],
+    lower_b: &[NotNan<T>; D],
+    upper_b: &[NotNan<T>; D],
+    boxsize: Option<&[NotNan<T>; D]>,
+) -> (T, T) {
+    // Initialize accumulator vars
+    let mut min_dist_sq: T = T::zero();
+    let mut max_dist_sq: T = T::zero();
+
+    for i in 0..D {
+        let (near, far) = separation_between_spaces(lower_a, upper_a, lower_b, upper_b, boxsize, i);
+        min_dist_sq += near.powi(2);
+        max_dist_sq += far.powi(2);
+    }
+
+    (min_dist_sq, max_dist_sq)
+}
+
+/// Calculate the smallest and largest absolute separations along dimension `i` between any
+/// point in the space defined by `lower_a` and `upper_a` and any point in the space defined by
+/// `lower_b` and `upper_b`, using minimum image separations if a `boxsize` is given.
+pub(crate) fn separation_between_spaces<T: Float, const D: usize>(
+    lower_a: &[NotNan<T>; D],
+    upper_a: &[NotNan<T>; D],
+    lower_b: &[NotNan<T>; D],
+    upper_b: &[NotNan<T>; D],
+    boxsize: Option<&[NotNan<T>; D]>,
+    i: usize,
+) -> (T, T) {
+    // Range of separations b - a along this dimension
+    // safety: made safe by const generic
+    let (sep_lower, sep_upper) = unsafe {
+        (
+            **lower_b.get_unchecked(i) - **upper_a.get_unchecked(i),
+            **upper_b.get_unchecked(i) - **lower_a.get_unchecked(i),
+        )
+    };
+
+    // Range of absolute separations along this dimension
+    let (near, far) = if sep_upper < T::zero() {
+        (-sep_upper, -sep_lower)
+    } else if sep_lower > T::zero() {
+        (sep_lower, sep_upper)
+    } else {
+        (T::zero(), sep_upper.max(-sep_lower))
+    };
+
+    match boxsize {
+        Some(boxsize) => {
+            // The minimum image separation increases up to half the boxsize, then decreases
+            // safety: made safe by const generic
+            let boxsize_component = **unsafe { boxsize.get_unchecked(i) };
+            let half = boxsize_component / T::from(2.0).unwrap();
+            let near_image = near.min(boxsize_component - near);
+            let far_image = far.min(boxsize_component - far);
+            if near <= half && half <= far {
+                (near_image.min(far_image), half)
+            } else {
+                (near_image.min(far_image), near_image.max(far_image))
+            }
+        }
+        None => (near, far),
+    }
+}
+
 /// This uses a short circuiting squared euclidean comparison.
 ///
 /// For example, in 3D if `(dx*dx + dy*dy) > current_best_squared`
@@ -199,7 +437,12 @@ mod tests {
     use approx_eq::assert_approx_eq;
     use ordered_float::NotNan;
 
-    use super::calc_dist_sq_to_space;
+    use super::{
+        calc_dist_sq_between_spaces, calc_dist_sq_to_space, calc_max_dist_sq_to_space, chebyshev,
+        chebyshev_periodic, manhattan, manhattan_periodic, weighted_squared_euclidean,
+        weighted_squared_euclidean_periodic,
+    };
+    use crate::metric::{Chebyshev, Manhattan, Metric, WeightedSquaredEuclidean};
 
     #[test]
     fn test_squared_euclidean() {
@@ -237,4 +480,119 @@ mod tests {
 
         assert_approx_eq!(calc_dist_sq_to_space(query, lower, upper), 0.0);
     }
+
+    #[test]
+    fn test_calc_max_dist_to_space_within() {
+        let query = &[NotNan::new(0.25).unwrap(); 3];
+        let lower = &[NotNan::new(0.0).unwrap(); 3];
+        let upper = &[NotNan::new(1.0).unwrap(); 3];
+
+        assert_approx_eq!(calc_max_dist_sq_to_space(query, lower, upper), 3.0 * 0.5625);
+    }
+
+    #[test]
+    fn test_calc_max_dist_to_space_above() {
+        let query = &[NotNan::new(2.0).unwrap(); 3];
+        let lower = &[NotNan::new(0.0).unwrap(); 3];
+        let upper = &[NotNan::new(1.0).unwrap(); 3];
+
+        assert_approx_eq!(calc_max_dist_sq_to_space(query, lower, upper), 12.0);
+    }
+
+    #[test]
+    fn test_calc_dist_between_spaces() {
+        let lower_a = &[NotNan::new(0.0).unwrap(); 2];
+        let upper_a = &[NotNan::new(1.0).unwrap(); 2];
+        let lower_b = &[NotNan::new(2.0).unwrap(), NotNan::new(0.5).unwrap()];
+        let upper_b = &[NotNan::new(3.0).unwrap(), NotNan::new(1.5).unwrap()];
+
+        let (min, max) = calc_dist_sq_between_spaces(lower_a, upper_a, lower_b, upper_b, None);
+        assert_approx_eq!(min, 1.0);
+        assert_approx_eq!(max, 9.0 + 2.25);
+    }
+
+    #[test]
+    fn test_calc_dist_between_spaces_periodic() {
+        let lower_a = &[NotNan::new(0.0).unwrap(); 2];
+        let upper_a = &[NotNan::new(1.0).unwrap(); 2];
+        let lower_b = &[NotNan::new(3.5).unwrap(), NotNan::new(0.5).unwrap()];
+        let upper_b = &[NotNan::new(4.0).unwrap(), NotNan::new(1.5).unwrap()];
+        let boxsize = &[NotNan::new(4.0).unwrap(); 2];
+
+        let (min, max) =
+            calc_dist_sq_between_spaces(lower_a, upper_a, lower_b, upper_b, Some(boxsize));
+        assert_approx_eq!(min, 0.0);
+        assert_approx_eq!(max, 2.25 + 2.25);
+    }
+
+    #[test]
+    fn test_manhattan_and_chebyshev() {
+        let a = [NotNan::new(1.0).unwrap(), NotNan::new(0.5).unwrap()];
+        let b = [NotNan::new(0.0).unwrap(), NotNan::new(0.0).unwrap()];
+        let boxsize = [NotNan::new(1.5).unwrap(); 2];
+
+        assert_approx_eq!(manhattan(&a, &b), 1.5);
+        assert_approx_eq!(chebyshev(&a, &b), 1.0);
+        assert_approx_eq!(manhattan_periodic(&a, &b, &boxsize), 1.0);
+        assert_approx_eq!(chebyshev_periodic(&a, &b, &boxsize), 0.5);
+    }
+
+    #[test]
+    fn test_weighted_squared_euclidean() {
+        let a = [NotNan::new(1.0).unwrap(), NotNan::new(0.5).unwrap()];
+        let b = [NotNan::new(0.0).unwrap(), NotNan::new(0.0).unwrap()];
+        let boxsize = [NotNan::new(1.5).unwrap(); 2];
+        let weights = [0.25, 4.0];
+
+        assert_approx_eq!(weighted_squared_euclidean(&a, &b, &weights), 1.25);
+        assert_approx_eq!(
+            weighted_squared_euclidean_periodic(&a, &b, &boxsize, &weights),
+            1.0625
+        );
+        assert_approx_eq!(
+            WeightedSquaredEuclidean::new(weights)
+                .unwrap()
+                .dist_to_space(
+                    &a,
+                    &[NotNan::new(2.0).unwrap(); 2],
+                    &[NotNan::new(3.0).unwrap(); 2]
+                ),
+            9.25
+        );
+    }
+
+    #[test]
+    fn test_manhattan_and_chebyshev_bounds() {
+        let query = &[
+            NotNan::new(2.0).unwrap(),
+            NotNan::new(0.5).unwrap(),
+            NotNan::new(-1.5).unwrap(),
+        ];
+        let lower = &[NotNan::new(0.0).unwrap(); 3];
+        let upper = &[NotNan::new(1.0).unwrap(); 3];
+
+        assert_approx_eq!(Manhattan.dist_to_space(query, lower, upper), 2.5);
+        assert_approx_eq!(Chebyshev.dist_to_space(query, lower, upper), 1.5);
+        assert_approx_eq!(Manhattan.max_dist_to_space(query, lower, upper), 5.0);
+        assert_approx_eq!(Chebyshev.max_dist_to_space(query, lower, upper), 2.5);
+
+        let lower_b = &[
+            NotNan::new(3.5).unwrap(),
+            NotNan::new(0.5).unwrap(),
+            NotNan::new(0.0).unwrap(),
+        ];
+        let upper_b = &[
+            NotNan::new(4.0).unwrap(),
+            NotNan::new(1.5).unwrap(),
+            NotNan::new(0.0).unwrap(),
+        ];
+        let boxsize = &[NotNan::new(4.0).unwrap(); 3];
+        let (min, max) = Manhattan.dist_between_spaces(lower, upper, lower_b, upper_b, None);
+        assert_approx_eq!(min, 2.5);
+        assert_approx_eq!(max, 4.0 + 1.5 + 1.0);
+        let (min, max) =
+            Chebyshev.dist_between_spaces(lower, upper, lower_b, upper_b, Some(boxsize));
+        assert_approx_eq!(min, 0.0);
+        assert_approx_eq!(max, 1.5);
+    }
 }
@@ -0,0 +1,173 @@
+use std::fmt::Debug;
+
+use crate::{
+    metric::Metric,
+    point::Float,
+    query_k::container::Container,
+    utils::{FnntwError, FnntwResult},
+    Tree,
+};
+use ordered_float::NotNan;
+
+/// The empirical cumulative distribution functions of the distances from a set of queries
+/// to their kth nearest neighbors, evaluated on a grid of distances.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KnnCdf<T> {
+    /// The requested neighbor ranks, where `k = 1` is the nearest neighbor
+    pub ks: Vec<usize>,
+    /// The distance grid on which the CDFs are evaluated
+    pub distances: Vec<T>,
+    /// `cdfs[i][j]` is the fraction of queries whose `ks[i]`th nearest neighbor is
+    /// no farther than `distances[j]`
+    pub cdfs: Vec<Vec<T>>,
+}
+
+impl<T: Float> KnnCdf<T> {
+    /// Returns the peaked CDFs, i.e. `min(cdf, 1 - cdf)`.
+    pub fn peaked_cdfs(&self) -> Vec<Vec<T>> {
+        self.cdfs
+            .iter()
+            .map(|cdf| cdf.iter().map(|c| c.min(T::one() - *c)).collect())
+            .collect()
+    }
+}
+
+impl<'t, T: Float + Debug, const D: usize, M: Metric<T, D>> Tree<'t, T, D, M> {
+    /// Computes the kNN-CDFs of the distances from every query in `queries` to its `ks[i]`th
+    /// nearest neighbors on the grid `distances`. If the tree has a boxsize, the minimum image
+    /// distance is used.
+    ///
+    /// Only a histogram of the kth neighbor distances is kept, so the `queries.len() * k`
+    /// neighbor distances are never materialized. Note that `distances` are always distances of
+    /// the tree's metric (e.g. not squared euclidean distances), even when the `sqrt-dist2`
+    /// feature is not enabled.
+    pub fn knn_cdf(
+        &self,
+        queries: &[[T; D]],
+        ks: &[usize],
+        distances: &[T],
+    ) -> FnntwResult<KnnCdf<T>, T> {
+        self.knn_cdf_with(
+            queries.len(),
+            |query_index| queries[query_index],
+            ks,
+            distances,
+        )
+    }
+
+    /// Same as [`Tree::knn_cdf`], except that the `num_queries` queries are generated on the
+    /// fly by `query_fn`, which is given the index of the query. This is useful for large sets
+    /// of volume-filling (e.g. random or grid) queries.
+    pub fn knn_cdf_with(
+        &self,
+        num_queries: usize,
+        query_fn: impl Fn(usize) -> [T; D] + Sync,
+        ks: &[usize],
+        distances: &[T],
+    ) -> FnntwResult<KnnCdf<T>, T> {
+        // Check for valid ks and distance grid
+        if ks.is_empty() || ks.iter().any(|k| *k == 0 || *k > self.input.len()) {
+            return Err(FnntwError::InvalidK);
+        }
+        if distances.is_empty()
+            || distances
+                .iter()
+                .any(|d| d.is_nan() || d.is_infinite() || *d < T::zero())
+            || distances.windows(2).any(|d| d[0] >= d[1])
+        {
+            return Err(FnntwError::InvalidDistances);
+        }
+        let max_k = *ks.iter().max().unwrap();
+        let distances2: Vec<T> = distances
+            .iter()
+            .map(|d| self.metric.to_reduced(*d))
+            .collect();
+
+        // Histogram of the kth neighbor distances, with the counts for ks[i] at
+        // i * distances.len()..(i + 1) * distances.len()
+        let empty_histogram = || vec![0_u64; ks.len() * distances.len()];
+        let buffers = || {
+            (
+                Container::new(max_k),
+                Vec::with_capacity(self.height_hint),
+                Vec::with_capacity(max_k),
+            )
+        };
+        let add_query = |histogram: &mut Vec<u64>,
+                         (container, points_to_check, dist2): &mut (_, _, _),
+                         query_index: usize|
+         -> FnntwResult<(), T> {
+            // Check for valid query point
+            let query = query_fn(query_index);
+            let query: &[NotNan<T>; D] = &self.check_query(&query)?;
+
+            self.fill_nearest_k(query, container, points_to_check);
+            container.drain_sorted_dist2(dist2);
+            self.add_kth_dist2(dist2, ks, &distances2, histogram);
+            Ok(())
+        };
+
+        #[cfg(feature = "parallel")]
+        let histogram = {
+            use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+
+            (0..num_queries)
+                .into_par_iter()
+                .try_fold(
+                    || (empty_histogram(), buffers()),
+                    |(mut histogram, mut buffers), query_index| {
+                        add_query(&mut histogram, &mut buffers, query_index)?;
+                        Ok((histogram, buffers))
+                    },
+                )
+                .map(|result| result.map(|(histogram, _)| histogram))
+                .try_reduce(empty_histogram, |mut a, b| {
+                    a.iter_mut().zip(b).for_each(|(a, b)| *a += b);
+                    Ok(a)
+                })?
+        };
+        #[cfg(not(feature = "parallel"))]
+        let histogram = {
+            let mut histogram = empty_histogram();
+            let mut buffers = buffers();
+            for query_index in 0..num_queries {
+                add_query(&mut histogram, &mut buffers, query_index)?;
+            }
+            histogram
+        };
+
+        // Accumulate histograms into CDFs
+        let num_queries = T::from(num_queries).unwrap();
+        let cdfs = histogram
+            .chunks_exact(distances.len())
+            .map(|counts| {
+                let mut cumulative = 0;
+                counts
+                    .iter()
+                    .map(|count| {
+                        cumulative += count;
+                        T::from(cumulative).unwrap() / num_queries
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Ok(KnnCdf {
+            ks: ks.to_vec(),
+            distances: distances.to_vec(),
+            cdfs,
+        })
+    }
+
+    /// Adds the `ks`th nearest neighbor squared distances from the sorted `dist2` to the
+    /// `histogram` over the squared distance grid `distances2`.
+    fn add_kth_dist2(&self, dist2: &[T], ks: &[usize], distances2: &[T], histogram: &mut [u64]) {
+        for (i, k) in ks.iter().enumerate() {
+            // First grid point no closer than the kth neighbor
+            let bin = distances2.partition_point(|d2| *d2 < dist2[k - 1]);
+            if bin < distances2.len() {
+                histogram[i * distances2.len() + bin] += 1;
+            }
+        }
+    }
+}
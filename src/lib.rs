@@ -1,6 +1,7 @@
 #![doc = include_str!("../README.md")]
 
 use likely_stable::likely;
+use metric::{Metric, SquaredEuclidean};
 pub use ordered_float::NotNan;
 use point::{Float, Point};
 
@@ -15,11 +16,25 @@ use std::{
 use num_format::{Locale, ToFormattedString};
 
 mod allocator;
+pub mod correlation;
+pub mod cosine;
 pub mod distance;
+pub mod knn_cdf;
+pub mod mahalanobis;
+pub mod metric;
 pub mod moms;
+mod owned;
+pub mod pair_count;
+mod periodic;
 pub mod point;
 pub mod query;
+pub mod query_ball;
+pub mod query_count;
 pub mod query_k;
+pub mod query_range;
+pub mod separation;
+pub mod spherical;
+pub mod triclinic;
 pub mod utils;
 
 use utils::*;
@@ -42,8 +57,9 @@ const NOT_FIRST: bool = false;
 const IS_LEFT: bool = true;
 const IS_RIGHT: bool = false;
 
-/// This [`Tree`] struct is the core struct that holds all nodes in the kdtree.
-pub struct Tree<'t, T: Float, const D: usize> {
+/// This [`Tree`] struct is the core struct that holds all nodes in the kdtree. Queries use
+/// the [`Metric`] `M`, which is the euclidean distance by default (see [`Tree::with_metric`]).
+pub struct Tree<'t, T: Float, const D: usize, M: Metric<T, D> = SquaredEuclidean> {
     /// Data in the tree. Here for user reference mainly. For example,
     /// to inspect the data that was used to build the tree.
     input: &'t [[T; D]],
@@ -63,12 +79,18 @@ pub struct Tree<'t, T: Float, const D: usize> {
     /// Root node
     root_node: Node<T, D>,
 
-    /// Optional boxsize for periodic queries.
+    /// Optional boxsize for periodic queries, which is infinite along nonperiodic axes.
     boxsize: Option<[NotNan<T>; D]>,
 
+    /// Lower corner of the periodic box, which is zero along nonperiodic axes.
+    origin: [NotNan<T>; D],
+
     /// Raw references to data points
     #[allow(unused)]
     data: Vec<Point<T, D>>,
+
+    /// Distance metric used by queries
+    metric: M,
 }
 
 #[derive(Debug)]
@@ -80,6 +102,8 @@ pub enum Node<T: Float, const D: usize> {
         right: usize,
         lower: [NotNan<T>; D],
         upper: [NotNan<T>; D],
+        /// Number of points in this stem's subtree, including the stem point
+        num_points: usize,
     },
     Leaf {
         points: Vec<Point<T, D>>,
@@ -91,8 +115,8 @@ pub enum Node<T: Float, const D: usize> {
 unsafe impl<T: Float, const D: usize> Send for Node<T, D> {}
 unsafe impl<T: Float, const D: usize> Sync for Node<T, D> {}
 
-unsafe impl<'t, T: Float, const D: usize> Send for Tree<'t, T, D> {}
-unsafe impl<'t, T: Float, const D: usize> Sync for Tree<'t, T, D> {}
+unsafe impl<'t, T: Float, const D: usize, M: Metric<T, D>> Send for Tree<'t, T, D, M> {}
+unsafe impl<'t, T: Float, const D: usize, M: Metric<T, D>> Sync for Tree<'t, T, D, M> {}
 
 impl<T: Float, const D: usize> Node<T, D> {
     fn is_stem(&self) -> bool {
@@ -130,6 +154,14 @@ impl<T: Float, const D: usize> Node<T, D> {
             Node::Stem { lower, upper, .. } => (lower, upper),
         }
     }
+
+    /// Returns the number of points in the subtree rooted at this node.
+    fn num_points(&self) -> usize {
+        match self {
+            Node::Leaf { points, .. } => points.len(),
+            Node::Stem { num_points, .. } => *num_points,
+        }
+    }
 }
 
 impl<'t, T: Float + Send + Debug, const D: usize> Tree<'t, T, D> {
@@ -260,6 +292,8 @@ impl<'t, T: Float + Send + Debug, const D: usize> Tree<'t, T, D> {
                 height_hint,
                 root_node,
                 boxsize: None,
+                origin: [NotNan::new(T::zero()).unwrap(); D],
+                metric: SquaredEuclidean,
             })
         })
     }
@@ -283,7 +317,8 @@ impl<'t, T: Float + Send + Debug, const D: usize> Tree<'t, T, D> {
         let split_dim = split_level % D;
 
         // Determine leaf-ness
-        let is_leaf = subset.len() <= leafsize;
+        let num_points = subset.len();
+        let is_leaf = num_points <= leafsize;
 
         // Get space bounds
         let (lower, upper) = {
@@ -443,6 +478,7 @@ impl<'t, T: Float + Send + Debug, const D: usize> Tree<'t, T, D> {
                 right: right_idx,
                 lower,
                 upper,
+                num_points,
             };
 
             #[cfg(feature = "timing")]
@@ -572,6 +608,8 @@ impl<'t, T: Float + Send + Debug, const D: usize> Tree<'t, T, D> {
                 height_hint,
                 root_node,
                 boxsize: None,
+                origin: [NotNan::new(T::zero()).unwrap(); D],
+                metric: SquaredEuclidean,
             })
         })
     }
@@ -594,7 +632,8 @@ impl<'t, T: Float + Send + Debug, const D: usize> Tree<'t, T, D> {
         let split_dim = split_level % D;
 
         // Determine leaf-ness
-        let is_leaf = subset.len() <= leafsize;
+        let num_points = subset.len();
+        let is_leaf = num_points <= leafsize;
 
         // Get space bounds
         let (lower, upper) = {
@@ -713,6 +752,7 @@ impl<'t, T: Float + Send + Debug, const D: usize> Tree<'t, T, D> {
                 right: right_handle,
                 lower,
                 upper,
+                num_points,
             };
 
             #[cfg(feature = "timing")]
@@ -727,7 +767,9 @@ impl<'t, T: Float + Send + Debug, const D: usize> Tree<'t, T, D> {
             stem_index
         }
     }
+}
 
+impl<'t, T: Float + Debug, const D: usize, M: Metric<T, D>> Tree<'t, T, D, M> {
     /// Returns the number of nodes in the tree
     /// The root node is contained in the struct, so must add one.
     pub fn size(&self) -> usize {
@@ -738,36 +780,115 @@ impl<'t, T: Float + Send + Debug, const D: usize> Tree<'t, T, D> {
         self.start
     }
 
-    /// Set the boxsize used for periodic queries
-    pub fn with_boxsize(mut self, boxsize: &[T; D]) -> FnntwResult<Self, T> {
+    /// Checks the query point `query` and, if the tree has a boxsize, wraps it into the
+    /// periodic box, so that periodic queries may lie anywhere.
+    #[inline(always)]
+    pub(crate) fn check_query(&self, query: &[T; D]) -> FnntwResult<[NotNan<T>; D], T> {
+        let query = check_point_return(query)?;
+        Ok(match self.boxsize {
+            Some(ref boxsize) => periodic::wrap(query, boxsize, &self.origin),
+            None => *query,
+        })
+    }
+
+    /// Set the boxsize used for periodic queries, for a box whose lower corner is the origin
+    pub fn with_boxsize(self, boxsize: &[T; D]) -> FnntwResult<Self, T> {
+        self.with_periodic_bounds(&boxsize.map(|side| Some((T::zero(), side))))
+    }
+
+    /// Set the boxsize used for periodic queries along some axes only, e.g. `[Some(lx),
+    /// Some(ly), None]` for a slab that is periodic in x and y but open in z. Queries only
+    /// consider the images along the periodic axes, and use the ordinary separation along the
+    /// others. If no axis is periodic, queries are nonperiodic.
+    pub fn with_periodic_axes(self, boxsize: &[Option<T>; D]) -> FnntwResult<Self, T> {
+        self.with_periodic_bounds(&boxsize.map(|side| side.map(|side| (T::zero(), side))))
+    }
+
+    /// Set the periodic box used for periodic queries by its `lower` and `upper` corners, e.g.
+    /// `[-l / 2; D]` and `[l / 2; D]` for a box of size `l` centered on the origin.
+    pub fn with_periodic_box(self, lower: &[T; D], upper: &[T; D]) -> FnntwResult<Self, T> {
+        let mut bounds = [None; D];
+        for i in 0..D {
+            bounds[i] = Some((lower[i], upper[i]));
+        }
+        self.with_periodic_bounds(&bounds)
+    }
+
+    /// Set the periodic box used for periodic queries by its lower and upper bounds along
+    /// every periodic axis, and `None` along every nonperiodic axis. This is the general form
+    /// of [`Tree::with_boxsize`], [`Tree::with_periodic_axes`] and [`Tree::with_periodic_box`].
+    ///
+    /// The data must lie within the box, but queries may lie anywhere: they are wrapped into
+    /// the box along every periodic axis before searching the tree.
+    pub fn with_periodic_bounds(mut self, bounds: &[Option<(T, T)>; D]) -> FnntwResult<Self, T> {
         // Get lower and upper bounds of data
         let (lower, upper) = self.root_node.get_bounds();
 
-        // Check that the data bounding box is in R_+^n
-        for component in lower {
-            if component.is_sign_negative() {
+        for i in 0..D {
+            let Some((box_lower, box_upper)) = bounds[i] else {
+                continue;
+            };
+            if [box_lower, box_upper]
+                .iter()
+                .any(|bound| bound.is_infinite() || bound.is_nan())
+            {
+                return Err(FnntwError::InvalidBoxsize);
+            }
+
+            // The box must have a positive, finite side along this axis
+            if box_upper <= box_lower || (box_upper - box_lower).is_infinite() {
+                return Err(FnntwError::InvalidBoxsize);
+            }
+
+            // Check that the data bounding box starts within the box along this axis
+            if *lower[i] < box_lower {
                 return Err(FnntwError::NegativeDataPeriodicQuery);
-            } else if component.is_infinite() || component.is_nan() {
+            } else if lower[i].is_infinite() || lower[i].is_nan() {
                 return Err(FnntwError::InvalidBoxsize);
             }
-        }
 
-        // Check that the specified boxsize encompasses the data
-        for i in 0..D {
-            if *upper[i] > boxsize[i] {
+            // Check that the specified box encompasses the data
+            if *upper[i] > box_upper {
                 return Err(FnntwError::SmallBoxsize);
-            } else if boxsize[i].is_infinite() || boxsize[i].is_nan() {
-                return Err(FnntwError::InvalidBoxsize);
             }
         }
 
+        // Nonperiodic axes are represented by an infinite boxsize, for which the minimum image
+        // separation is the ordinary separation.
         // safety: just checked all properties that that NotNan assumes
-        unsafe {
-            self.boxsize = Some(std::mem::transmute::<&[T; D], &[NotNan<T>; D]>(boxsize).clone());
-        }
+        self.boxsize = bounds.iter().any(Option::is_some).then(|| {
+            bounds.map(|bounds| unsafe {
+                NotNan::new_unchecked(bounds.map_or(T::infinity(), |(lower, upper)| upper - lower))
+            })
+        });
+        self.origin = bounds.map(|bounds| unsafe {
+            NotNan::new_unchecked(bounds.map_or(T::zero(), |(lower, _)| lower))
+        });
         Ok(self)
     }
 
+    /// Set the distance metric used by queries. The tree does not need to be rebuilt, since
+    /// its nodes only depend on the coordinates of the data.
+    pub fn with_metric<N: Metric<T, D>>(self, metric: N) -> Tree<'t, T, D, N> {
+        Tree {
+            input: self.input,
+            start: self.start,
+            leafsize: self.leafsize,
+            nodes: self.nodes,
+            height_hint: self.height_hint,
+            root_node: self.root_node,
+            boxsize: self.boxsize,
+            origin: self.origin,
+            data: self.data,
+            metric,
+        }
+    }
+
+    /// Returns the distance metric used by queries.
+    pub fn metric(&self) -> &M {
+        &self.metric
+    }
+
     pub fn get_data(&self) -> &[[T; D]] {
         self.input
     }
@@ -0,0 +1,226 @@
+use std::fmt::Debug;
+
+#[cfg(not(feature = "no-position"))]
+use ordered_float::NotNan;
+
+use crate::{
+    owned::OwnedTree,
+    point::Float,
+    utils::{FnntwError, FnntwResult, QueryBallResult, QueryKResult, QueryResult},
+    Tree,
+};
+
+/// The Mahalanobis distance for a fixed, positive definite covariance matrix `S`, i.e.
+/// `sqrt((a - b)^T S^-1 (a - b))`.
+///
+/// This is not a [`Metric`](crate::metric::Metric), since it does not separate into distances
+/// along each axis. Instead, with the Cholesky decomposition `S = L L^T`, it is the euclidean
+/// distance between the whitened points `L^-1 a` and `L^-1 b`, which is how a
+/// [`MahalanobisTree`] is built and queried.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mahalanobis<T, const D: usize> {
+    /// Lower triangular Cholesky factor `L` of the covariance matrix
+    cholesky: [[T; D]; D],
+}
+
+impl<T: Float, const D: usize> Mahalanobis<T, D> {
+    /// Creates the Mahalanobis distance for the `covariance` matrix, which must be symmetric and
+    /// positive definite. Only the lower triangle of `covariance` is used.
+    pub fn new(covariance: &[[T; D]; D]) -> FnntwResult<Self, T> {
+        if covariance
+            .iter()
+            .flatten()
+            .any(|c| c.is_nan() || c.is_infinite())
+        {
+            return Err(FnntwError::InvalidCovariance);
+        }
+
+        // Cholesky–Banachiewicz decomposition
+        let mut cholesky = [[T::zero(); D]; D];
+        for i in 0..D {
+            for j in 0..=i {
+                let sum = (0..j).fold(covariance[i][j], |sum, k| {
+                    sum - cholesky[i][k] * cholesky[j][k]
+                });
+                if i == j {
+                    // Not positive definite
+                    if sum.is_nan() || sum <= T::zero() {
+                        return Err(FnntwError::InvalidCovariance);
+                    }
+                    cholesky[i][i] = sum.sqrt();
+                } else {
+                    cholesky[i][j] = sum / cholesky[j][j];
+                }
+            }
+        }
+
+        Ok(Mahalanobis { cholesky })
+    }
+
+    /// The lower triangular Cholesky factor `L` of the covariance matrix.
+    pub fn cholesky(&self) -> &[[T; D]; D] {
+        &self.cholesky
+    }
+
+    /// Transforms `point` into the whitened space, i.e. returns `L^-1 point`, in which the
+    /// Mahalanobis distance is the euclidean distance.
+    pub fn whiten(&self, point: &[T; D]) -> [T; D] {
+        // Forward substitution
+        let mut whitened = [T::zero(); D];
+        for i in 0..D {
+            let sum = (0..i).fold(point[i], |sum, k| sum - self.cholesky[i][k] * whitened[k]);
+            whitened[i] = sum / self.cholesky[i][i];
+        }
+        whitened
+    }
+
+    /// Returns the Mahalanobis distance between `a` and `b`.
+    pub fn dist(&self, a: &[T; D], b: &[T; D]) -> T {
+        let (a, b) = (self.whiten(a), self.whiten(b));
+        a.iter()
+            .zip(&b)
+            .fold(T::zero(), |acc, (a, b)| acc + (*a - *b).powi(2))
+            .sqrt()
+    }
+}
+
+/// A kdtree for queries with the [`Mahalanobis`] distance. The tree is built on a whitened
+/// copy of the data, and queries are whitened before searching it, so that all queries take
+/// and return points, distances and indices of the original data.
+///
+/// As for the euclidean [`Tree`], distances are squared unless the `sqrt-dist2` feature is
+/// enabled. Periodic boundary conditions are not supported, since the whitened space is no
+/// longer an axis-aligned box.
+pub struct MahalanobisTree<'t, T: Float, const D: usize> {
+    /// Data used to build the tree
+    input: &'t [[T; D]],
+
+    /// The distance used by queries
+    mahalanobis: Mahalanobis<T, D>,
+
+    /// Tree built on (and owning) the whitened data
+    tree: OwnedTree<'t, T, D>,
+}
+
+impl<'t, T: Float + Debug, const D: usize> MahalanobisTree<'t, T, D> {
+    /// Create a new [MahalanobisTree] for the `covariance` matrix using a nonparallel build.
+    pub fn new(
+        input: &'t [[T; D]],
+        covariance: &[[T; D]; D],
+        leafsize: usize,
+    ) -> FnntwResult<MahalanobisTree<'t, T, D>, T> {
+        let mahalanobis = Mahalanobis::new(covariance)?;
+        let whitened: Vec<[T; D]> = input.iter().map(|p| mahalanobis.whiten(p)).collect();
+        let tree = OwnedTree::new(whitened, leafsize)?;
+
+        Ok(MahalanobisTree {
+            input,
+            mahalanobis,
+            tree,
+        })
+    }
+
+    /// Create a new [MahalanobisTree] for the `covariance` matrix using a parallel build. See
+    /// [`Tree::new_parallel`].
+    pub fn new_parallel(
+        input: &'t [[T; D]],
+        covariance: &[[T; D]; D],
+        leafsize: usize,
+        par_split_level: usize,
+    ) -> FnntwResult<MahalanobisTree<'t, T, D>, T> {
+        let mahalanobis = Mahalanobis::new(covariance)?;
+        let whitened: Vec<[T; D]> = input.iter().map(|p| mahalanobis.whiten(p)).collect();
+        let tree = OwnedTree::new_parallel(whitened, leafsize, par_split_level)?;
+
+        Ok(MahalanobisTree {
+            input,
+            mahalanobis,
+            tree,
+        })
+    }
+
+    /// Returns the distance used by queries.
+    pub fn mahalanobis(&self) -> &Mahalanobis<T, D> {
+        &self.mahalanobis
+    }
+
+    /// Returns the data used to build the tree, in the original (not whitened) space.
+    pub fn get_data(&self) -> &[[T; D]] {
+        self.input
+    }
+
+    /// Returns the euclidean tree built on the whitened data, e.g. for queries not provided by
+    /// [MahalanobisTree]. Its indices refer to the original data, but queries must first be
+    /// whitened with [`Mahalanobis::whiten`].
+    pub fn whitened_tree(&self) -> &Tree<'_, T, D> {
+        self.tree.tree()
+    }
+
+    /// Finds the nearest neighbor of the `query`. See [`Tree::query_nearest`].
+    pub fn query_nearest<'q>(&'q self, query: &[T; D]) -> FnntwResult<QueryResult<'q, T, D>, T> {
+        let result = self
+            .whitened_tree()
+            .query_nearest(&self.mahalanobis.whiten(query))?;
+
+        #[cfg(feature = "no-position")]
+        return Ok(result);
+        #[cfg(not(feature = "no-position"))]
+        return Ok((result.0, result.1, self.position(result.1)));
+    }
+
+    /// Finds the `k` nearest neighbors of the `query`. See [`Tree::query_nearest_k`].
+    pub fn query_nearest_k(
+        &self,
+        query: &[T; D],
+        k: usize,
+    ) -> FnntwResult<QueryKResult<'t, T, D>, T> {
+        let query = self.mahalanobis.whiten(query);
+        #[allow(unused_mut)]
+        let mut result = self.whitened_tree().query_nearest_k(&query, k)?;
+
+        #[cfg(not(feature = "no-position"))]
+        self.replace_positions(&result.1, &mut result.2);
+        Ok(result)
+    }
+
+    /// Finds all points within `radius` (a Mahalanobis distance) of the `query`. See
+    /// [`Tree::query_ball_point`].
+    pub fn query_ball_point(
+        &self,
+        query: &[T; D],
+        radius: T,
+    ) -> FnntwResult<QueryBallResult<'t, T, D>, T> {
+        let query = self.mahalanobis.whiten(query);
+        #[allow(unused_mut)]
+        let mut result = self.whitened_tree().query_ball_point(&query, radius)?;
+
+        #[cfg(not(feature = "no-position"))]
+        self.replace_positions(&result.1, &mut result.2);
+        Ok(result)
+    }
+
+    /// Counts the points within `radius` (a Mahalanobis distance) of the `query`. See
+    /// [`Tree::count_within`].
+    pub fn count_within(&self, query: &[T; D], radius: T) -> FnntwResult<u64, T> {
+        self.whitened_tree()
+            .count_within(&self.mahalanobis.whiten(query), radius)
+    }
+
+    /// Returns the original position of the point with the given `index`.
+    #[cfg(not(feature = "no-position"))]
+    fn position(&self, index: u64) -> &'t [NotNan<T>; D] {
+        // safety: the whitened data was checked when building the tree, and any nan or infinite
+        // component of the original data would have carried over into the whitened data
+        unsafe {
+            std::mem::transmute::<&'t [T; D], &'t [NotNan<T>; D]>(&self.input[index as usize])
+        }
+    }
+
+    /// Replaces the whitened positions of the neighbors with the given `indices`.
+    #[cfg(not(feature = "no-position"))]
+    fn replace_positions(&self, indices: &[u64], positions: &mut [[NotNan<T>; D]]) {
+        for (index, position) in indices.iter().zip(positions) {
+            *position = *self.position(*index);
+        }
+    }
+}
@@ -0,0 +1,373 @@
+use std::fmt::Debug;
+
+use ordered_float::NotNan;
+
+use crate::{
+    distance::*,
+    point::Float,
+    utils::{FnntwError, FnntwResult},
+};
+
+/// A distance metric that the queries of a [`Tree`](crate::Tree) are generic over.
+///
+/// Queries work with a "reduced" distance that is monotonic in the true distance but cheaper
+/// to compute, e.g. the squared distance for [`SquaredEuclidean`]. Metrics are defined by the
+/// reduced distance of a separation along a single axis and by how these are accumulated
+/// across axes, from which the reduced distances between points and to the bounding boxes of
+/// the tree's nodes are derived. Any of these may be overridden with a faster implementation.
+///
+/// Note that the tree only prunes correctly if the reduced distance only increases as the
+/// separation along any axis increases, and if it is no smaller than the reduced distance
+/// along any single axis, i.e. if `accumulate(acc, axis_dist) >= acc.max(axis_dist)`.
+pub trait Metric<T: Float, const D: usize>: Clone + Debug + Send + Sync {
+    /// The reduced distance of a (nonnegative) separation `dx` along `axis` alone.
+    fn axis_dist(&self, axis: usize, dx: T) -> T;
+
+    /// Adds the reduced distance along one more axis to the accumulated reduced distance `acc`.
+    #[inline(always)]
+    fn accumulate(&self, acc: T, axis_dist: T) -> T {
+        acc + axis_dist
+    }
+
+    /// Converts a reduced distance into the distance.
+    fn to_distance(&self, reduced: T) -> T;
+
+    /// Converts a distance into the reduced distance.
+    fn to_reduced(&self, distance: T) -> T;
+
+    /// Converts a reduced distance into the distance returned by queries, i.e. the distance
+    /// if the `sqrt-dist2` feature is enabled and the reduced distance otherwise.
+    #[inline(always)]
+    fn process(&self, reduced: T) -> T {
+        #[cfg(feature = "sqrt-dist2")]
+        return self.to_distance(reduced);
+        #[cfg(not(feature = "sqrt-dist2"))]
+        return reduced;
+    }
+
+    /// The reduced distance between `a` and `b`.
+    #[inline(always)]
+    fn dist(&self, a: &[NotNan<T>; D], b: &[NotNan<T>; D]) -> T {
+        (0..D).fold(T::zero(), |acc, i| {
+            self.accumulate(acc, self.axis_dist(i, (*a[i] - *b[i]).abs()))
+        })
+    }
+
+    /// The reduced distance between `a` and `b`, along with the reduced distance along `axis`
+    /// alone and the reduced distance accumulated over all other axes.
+    #[inline(always)]
+    fn dist_axis(&self, a: &[NotNan<T>; D], b: &[NotNan<T>; D], axis: usize) -> (T, T, T) {
+        let ax = self.axis_dist(axis, (*a[axis] - *b[axis]).abs());
+        let nonax = (0..D).filter(|&i| i != axis).fold(T::zero(), |acc, i| {
+            self.accumulate(acc, self.axis_dist(i, (*a[i] - *b[i]).abs()))
+        });
+        (self.accumulate(nonax, ax), ax, nonax)
+    }
+
+    /// The reduced minimum image distance between `a` and `b` in a periodic box of size
+    /// `boxsize`. Both points must lie within the box.
+    #[inline(always)]
+    fn dist_periodic(&self, a: &[NotNan<T>; D], b: &[NotNan<T>; D], boxsize: &[NotNan<T>; D]) -> T {
+        (0..D).fold(T::zero(), |acc, i| {
+            let dx = (*a[i] - *b[i]).abs();
+            self.accumulate(acc, self.axis_dist(i, dx.min(*boxsize[i] - dx)))
+        })
+    }
+
+    /// A lower bound on the reduced distance from `query` to any point in the space defined
+    /// by `lower` and `upper`.
+    #[inline(always)]
+    fn dist_to_space(
+        &self,
+        query: &[NotNan<T>; D],
+        lower: &[NotNan<T>; D],
+        upper: &[NotNan<T>; D],
+    ) -> T {
+        (0..D).fold(T::zero(), |acc, i| {
+            let dx = (*lower[i] - *query[i])
+                .max(*query[i] - *upper[i])
+                .max(T::zero());
+            self.accumulate(acc, self.axis_dist(i, dx))
+        })
+    }
+
+    /// An upper bound on the reduced distance from `query` to any point in the space defined
+    /// by `lower` and `upper`.
+    #[inline(always)]
+    fn max_dist_to_space(
+        &self,
+        query: &[NotNan<T>; D],
+        lower: &[NotNan<T>; D],
+        upper: &[NotNan<T>; D],
+    ) -> T {
+        (0..D).fold(T::zero(), |acc, i| {
+            let dx = (*query[i] - *lower[i])
+                .abs()
+                .max((*query[i] - *upper[i]).abs());
+            self.accumulate(acc, self.axis_dist(i, dx))
+        })
+    }
+
+    /// Lower and upper bounds on the reduced distance between any point in the space defined by
+    /// `lower_a` and `upper_a` and any point in the space defined by `lower_b` and `upper_b`.
+    ///
+    /// If a `boxsize` is given, minimum image distances are used, in which case both spaces must
+    /// lie within the box.
+    #[inline(always)]
+    fn dist_between_spaces(
+        &self,
+        lower_a: &[NotNan<T>; D],
+        upper_a: &[NotNan<T>; D],
+        lower_b: &[NotNan<T>; D],
+        upper_b: &[NotNan<T>; D],
+        boxsize: Option<&[NotNan<T>; D]>,
+    ) -> (T, T) {
+        (0..D).fold((T::zero(), T::zero()), |(min, max), i| {
+            let (near, far) =
+                separation_between_spaces(lower_a, upper_a, lower_b, upper_b, boxsize, i);
+            (
+                self.accumulate(min, self.axis_dist(i, near)),
+                self.accumulate(max, self.axis_dist(i, far)),
+            )
+        })
+    }
+}
+
+/// The euclidean metric, with the squared euclidean distance as the reduced distance.
+/// This is the default metric of a [`Tree`](crate::Tree).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SquaredEuclidean;
+
+impl<T: Float, const D: usize> Metric<T, D> for SquaredEuclidean {
+    #[inline(always)]
+    fn axis_dist(&self, _axis: usize, dx: T) -> T {
+        dx * dx
+    }
+
+    #[inline(always)]
+    fn to_distance(&self, reduced: T) -> T {
+        reduced.sqrt()
+    }
+
+    #[inline(always)]
+    fn to_reduced(&self, distance: T) -> T {
+        distance.powi(2)
+    }
+
+    #[inline(always)]
+    fn dist(&self, a: &[NotNan<T>; D], b: &[NotNan<T>; D]) -> T {
+        squared_euclidean(a, b)
+    }
+
+    #[inline(always)]
+    fn dist_axis(&self, a: &[NotNan<T>; D], b: &[NotNan<T>; D], axis: usize) -> (T, T, T) {
+        squared_euclidean_axis(a, b, axis)
+    }
+
+    #[inline(always)]
+    fn dist_periodic(&self, a: &[NotNan<T>; D], b: &[NotNan<T>; D], boxsize: &[NotNan<T>; D]) -> T {
+        squared_euclidean_periodic(a, b, boxsize)
+    }
+
+    #[inline(always)]
+    fn dist_to_space(
+        &self,
+        query: &[NotNan<T>; D],
+        lower: &[NotNan<T>; D],
+        upper: &[NotNan<T>; D],
+    ) -> T {
+        calc_dist_sq_to_space(query, lower, upper)
+    }
+
+    #[inline(always)]
+    fn max_dist_to_space(
+        &self,
+        query: &[NotNan<T>; D],
+        lower: &[NotNan<T>; D],
+        upper: &[NotNan<T>; D],
+    ) -> T {
+        calc_max_dist_sq_to_space(query, lower, upper)
+    }
+
+    #[inline(always)]
+    fn dist_between_spaces(
+        &self,
+        lower_a: &[NotNan<T>; D],
+        upper_a: &[NotNan<T>; D],
+        lower_b: &[NotNan<T>; D],
+        upper_b: &[NotNan<T>; D],
+        boxsize: Option<&[NotNan<T>; D]>,
+    ) -> (T, T) {
+        calc_dist_sq_between_spaces(lower_a, upper_a, lower_b, upper_b, boxsize)
+    }
+}
+
+/// The manhattan (L1) metric, i.e. the sum of the absolute separations along every axis.
+/// The reduced distance is the distance itself, so distances are returned as is regardless
+/// of the `sqrt-dist2` feature.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Manhattan;
+
+impl<T: Float, const D: usize> Metric<T, D> for Manhattan {
+    #[inline(always)]
+    fn axis_dist(&self, _axis: usize, dx: T) -> T {
+        dx
+    }
+
+    #[inline(always)]
+    fn to_distance(&self, reduced: T) -> T {
+        reduced
+    }
+
+    #[inline(always)]
+    fn to_reduced(&self, distance: T) -> T {
+        distance
+    }
+
+    #[inline(always)]
+    fn dist(&self, a: &[NotNan<T>; D], b: &[NotNan<T>; D]) -> T {
+        manhattan(a, b)
+    }
+
+    #[inline(always)]
+    fn dist_periodic(&self, a: &[NotNan<T>; D], b: &[NotNan<T>; D], boxsize: &[NotNan<T>; D]) -> T {
+        manhattan_periodic(a, b, boxsize)
+    }
+}
+
+/// The chebyshev (L-infinity) metric, i.e. the largest absolute separation along any axis.
+/// The reduced distance is the distance itself, so distances are returned as is regardless
+/// of the `sqrt-dist2` feature.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Chebyshev;
+
+impl<T: Float, const D: usize> Metric<T, D> for Chebyshev {
+    #[inline(always)]
+    fn axis_dist(&self, _axis: usize, dx: T) -> T {
+        dx
+    }
+
+    #[inline(always)]
+    fn accumulate(&self, acc: T, axis_dist: T) -> T {
+        acc.max(axis_dist)
+    }
+
+    #[inline(always)]
+    fn to_distance(&self, reduced: T) -> T {
+        reduced
+    }
+
+    #[inline(always)]
+    fn to_reduced(&self, distance: T) -> T {
+        distance
+    }
+
+    #[inline(always)]
+    fn dist(&self, a: &[NotNan<T>; D], b: &[NotNan<T>; D]) -> T {
+        chebyshev(a, b)
+    }
+
+    #[inline(always)]
+    fn dist_periodic(&self, a: &[NotNan<T>; D], b: &[NotNan<T>; D], boxsize: &[NotNan<T>; D]) -> T {
+        chebyshev_periodic(a, b, boxsize)
+    }
+}
+
+/// The euclidean metric with a (positive) weight per axis, i.e. the square root of
+/// `sum_i weights[i] * dx_i^2`, with its square as the reduced distance. This is equivalent to
+/// the euclidean metric on data whose coordinates are scaled by the square roots of the weights.
+///
+/// Note that with a boxsize, the radius of periodic queries must be smaller than the distance
+/// spanned by half of the boxsize along every axis, which is smaller than half of the boxsize along
+/// axes whose weight is less than one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeightedSquaredEuclidean<T, const D: usize> {
+    weights: [T; D],
+}
+
+impl<T: Float + Debug, const D: usize> WeightedSquaredEuclidean<T, D> {
+    /// Creates the weighted euclidean metric, with `weights` that must be finite and positive.
+    pub fn new(weights: [T; D]) -> FnntwResult<Self, T> {
+        if weights
+            .iter()
+            .any(|w| w.is_nan() || w.is_infinite() || *w <= T::zero())
+        {
+            return Err(FnntwError::InvalidMetricWeights);
+        }
+        Ok(WeightedSquaredEuclidean { weights })
+    }
+
+    /// The weight of every axis.
+    pub fn weights(&self) -> &[T; D] {
+        &self.weights
+    }
+}
+
+impl<T: Float + Debug, const D: usize> Metric<T, D> for WeightedSquaredEuclidean<T, D> {
+    #[inline(always)]
+    fn axis_dist(&self, axis: usize, dx: T) -> T {
+        // safety: axes are always less than D
+        *unsafe { self.weights.get_unchecked(axis) } * dx * dx
+    }
+
+    #[inline(always)]
+    fn to_distance(&self, reduced: T) -> T {
+        reduced.sqrt()
+    }
+
+    #[inline(always)]
+    fn to_reduced(&self, distance: T) -> T {
+        distance.powi(2)
+    }
+
+    #[inline(always)]
+    fn dist(&self, a: &[NotNan<T>; D], b: &[NotNan<T>; D]) -> T {
+        weighted_squared_euclidean(a, b, &self.weights)
+    }
+
+    #[inline(always)]
+    fn dist_periodic(&self, a: &[NotNan<T>; D], b: &[NotNan<T>; D], boxsize: &[NotNan<T>; D]) -> T {
+        weighted_squared_euclidean_periodic(a, b, boxsize, &self.weights)
+    }
+}
+
+/// The minkowski metric of order `p`, i.e. the `p`-norm of the separation, with the `p`th power
+/// of the distance as the reduced distance. Any finite `p >= 1` (including fractional `p`) is
+/// supported; see [`Chebyshev`] for `p = inf`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Minkowski<T> {
+    p: T,
+}
+
+impl<T: Float + Debug> Minkowski<T> {
+    /// Creates the minkowski metric of order `p`, which must be finite and at least one, since
+    /// for smaller `p` the `p`-norm does not satisfy the triangle inequality.
+    pub fn new(p: T) -> FnntwResult<Self, T> {
+        if p.is_nan() || p.is_infinite() || p < T::one() {
+            return Err(FnntwError::InvalidMinkowskiP);
+        }
+        Ok(Minkowski { p })
+    }
+
+    /// The order of the metric.
+    pub fn p(&self) -> T {
+        self.p
+    }
+}
+
+impl<T: Float + Debug, const D: usize> Metric<T, D> for Minkowski<T> {
+    #[inline(always)]
+    fn axis_dist(&self, _axis: usize, dx: T) -> T {
+        dx.powf(self.p)
+    }
+
+    #[inline(always)]
+    fn to_distance(&self, reduced: T) -> T {
+        reduced.powf(self.p.recip())
+    }
+
+    #[inline(always)]
+    fn to_reduced(&self, distance: T) -> T {
+        distance.powf(self.p)
+    }
+}
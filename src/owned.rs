@@ -0,0 +1,52 @@
+use std::fmt::Debug;
+
+use crate::{point::Float, utils::FnntwResult, Tree};
+
+/// A [`Tree`] that owns the data it is built on, for trees that are built on a transformed
+/// copy of the input data (e.g. whitened, normalized or unit vectors).
+///
+/// The tree borrows `data` for `'t`, which outlives the struct itself. This is sound because
+/// the heap allocation of `data` does not move with the struct, `data` is never mutated (or
+/// exposed mutably), the tree is declared first so that it is dropped first, and the tree is
+/// only handed out with its lifetime shortened to that of the borrow of the struct.
+pub(crate) struct OwnedTree<'t, T: Float, const D: usize> {
+    /// Tree built on `data`. Must be declared first in order to be dropped first.
+    tree: Tree<'t, T, D>,
+
+    /// The data the tree is built on
+    data: Vec<[T; D]>,
+}
+
+impl<'t, T: Float + Debug, const D: usize> OwnedTree<'t, T, D> {
+    /// Builds a tree on `data` using a nonparallel build. See [`Tree::new`].
+    pub(crate) fn new(data: Vec<[T; D]>, leafsize: usize) -> FnntwResult<Self, T> {
+        // safety: see the struct documentation
+        let borrowed = unsafe { std::mem::transmute::<&[[T; D]], &'t [[T; D]]>(&data) };
+        let tree = Tree::<'t, T, D>::new(borrowed, leafsize)?;
+
+        Ok(OwnedTree { tree, data })
+    }
+
+    /// Builds a tree on `data` using a parallel build. See [`Tree::new_parallel`].
+    pub(crate) fn new_parallel(
+        data: Vec<[T; D]>,
+        leafsize: usize,
+        par_split_level: usize,
+    ) -> FnntwResult<Self, T> {
+        // safety: see the struct documentation
+        let borrowed = unsafe { std::mem::transmute::<&[[T; D]], &'t [[T; D]]>(&data) };
+        let tree = Tree::<'t, T, D>::new_parallel(borrowed, leafsize, par_split_level)?;
+
+        Ok(OwnedTree { tree, data })
+    }
+
+    /// Returns the tree, borrowed for no longer than the struct.
+    pub(crate) fn tree(&self) -> &Tree<'_, T, D> {
+        &self.tree
+    }
+
+    /// Returns the data the tree is built on.
+    pub(crate) fn data(&self) -> &[[T; D]] {
+        &self.data
+    }
+}
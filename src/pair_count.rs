@@ -0,0 +1,358 @@
+use std::{
+    fmt::Debug,
+    ops::{AddAssign, Mul, Sub},
+};
+
+use crate::{
+    metric::Metric,
+    point::{Float, Point},
+    utils::{FnntwError, FnntwResult},
+    Node, Tree,
+};
+use ordered_float::NotNan;
+
+impl<'t, T: Float + Debug, const D: usize, M: Metric<T, D>> Tree<'t, T, D, M> {
+    /// Counts all pairs of points `(a, b)`, with `a` in this tree and `b` in `other`, whose
+    /// separation falls in each of the radial bins defined by `bin_edges`. The count at index
+    /// `i` of the result is the number of pairs with `bin_edges[i] <= r < bin_edges[i + 1]`.
+    ///
+    /// If the trees have a boxsize (which must be the same for both), the minimum image
+    /// separation is used. Note that `bin_edges` are always distances of the tree's metric (e.g.
+    /// not squared euclidean distances), and that when a tree is paired with itself every
+    /// distinct pair is counted twice and every point is paired with itself at zero separation.
+    pub fn count_pairs(
+        &self,
+        other: &Tree<'_, T, D, M>,
+        bin_edges: &[T],
+    ) -> FnntwResult<Vec<u64>, T> {
+        let edges2 = self.check_pair_count(other, bin_edges)?;
+
+        Ok(self.pair_count_by(other, &edges2, &Unweighted, &Unweighted))
+    }
+
+    /// Same as [`Tree::count_pairs`], except every pair `(a, b)` contributes the product of
+    /// their weights `weights[a] * other_weights[b]` instead of one.
+    pub fn count_pairs_weighted(
+        &self,
+        other: &Tree<'_, T, D, M>,
+        bin_edges: &[T],
+        weights: &[T],
+        other_weights: &[T],
+    ) -> FnntwResult<Vec<T>, T> {
+        let edges2 = self.check_pair_count(other, bin_edges)?;
+        if weights.len() != self.input.len() || other_weights.len() != other.input.len() {
+            return Err(FnntwError::WeightsLengthMismatch);
+        }
+
+        Ok(self.pair_count_by(
+            other,
+            &edges2,
+            &Weighted::new(self, weights),
+            &Weighted::new(other, other_weights),
+        ))
+    }
+
+    /// Checks that `bin_edges` are valid and that the two trees share a boxsize, returning the
+    /// bin edges as reduced distances of the metric.
+    pub(crate) fn check_pair_count(
+        &self,
+        other: &Tree<'_, T, D, M>,
+        bin_edges: &[T],
+    ) -> FnntwResult<Vec<T>, T> {
+        if bin_edges.len() < 2
+            || bin_edges
+                .iter()
+                .any(|edge| edge.is_nan() || edge.is_infinite() || *edge < T::zero())
+            || bin_edges.windows(2).any(|edges| edges[0] >= edges[1])
+        {
+            return Err(FnntwError::InvalidBinEdges);
+        }
+        if self.boxsize != other.boxsize || self.origin != other.origin {
+            return Err(FnntwError::BoxsizeMismatch);
+        }
+
+        Ok(bin_edges
+            .iter()
+            .map(|edge| self.metric.to_reduced(*edge))
+            .collect())
+    }
+
+    fn pair_count_by<W: PairWeights<T, D>>(
+        &self,
+        other: &Tree<'_, T, D, M>,
+        edges2: &[T],
+        weights: &W,
+        other_weights: &W,
+    ) -> Vec<W::Weight> {
+        let dual_tree = DualTree {
+            trees: (self.nodes.as_slice(), other.nodes.as_slice()),
+            weights: (weights, other_weights),
+            edges2,
+            boxsize: self.boxsize.as_ref(),
+            metric: &self.metric,
+        };
+
+        // Number of pairs closer than every bin edge
+        let mut cumulative = vec![W::zero(); edges2.len()];
+        dual_tree.traverse(
+            Subtree::root(self),
+            Subtree::root(other),
+            0,
+            edges2.len(),
+            &mut cumulative,
+        );
+
+        cumulative
+            .windows(2)
+            .map(|cumulative| cumulative[1] - cumulative[0])
+            .collect()
+    }
+}
+
+/// A subtree of a [`Tree`] visited during a dual-tree traversal. The point of a stem is
+/// separated from its children so that every point belongs to exactly one subtree.
+#[derive(Clone, Copy)]
+enum Subtree<'i, T: Float, const D: usize> {
+    /// A node along with its index in the tree's nodes (or the number of nodes for the root).
+    Node(&'i Node<T, D>, usize),
+    Point(&'i Point<T, D>),
+}
+
+impl<'i, T: Float, const D: usize> Subtree<'i, T, D> {
+    fn root<M: Metric<T, D>>(tree: &'i Tree<'_, T, D, M>) -> Self {
+        Subtree::Node(&tree.root_node, tree.nodes.len())
+    }
+
+    fn bounds(&self) -> (&'i [NotNan<T>; D], &'i [NotNan<T>; D]) {
+        match self {
+            Subtree::Node(node, _) => node.get_bounds(),
+            Subtree::Point(point) => (point.position(), point.position()),
+        }
+    }
+
+    fn num_points(&self) -> usize {
+        match self {
+            Subtree::Node(node, _) => node.num_points(),
+            Subtree::Point(_) => 1,
+        }
+    }
+
+    /// Splits a stem into its point and children, or returns `None` for leaves and points.
+    fn children(&self, nodes: &'i [Node<T, D>]) -> Option<[Self; 3]> {
+        match self {
+            Subtree::Node(
+                Node::Stem {
+                    point, left, right, ..
+                },
+                _,
+            ) => Some([
+                Subtree::Point(point),
+                // safety: indices are valid by construction, with the atomic lock on Vec<Node>
+                Subtree::Node(unsafe { nodes.get_unchecked(*left) }, *left),
+                Subtree::Node(unsafe { nodes.get_unchecked(*right) }, *right),
+            ]),
+            _ => None,
+        }
+    }
+
+    /// The points of a leaf or point; panics if called on a stem.
+    fn points(&self) -> &'i [Point<T, D>] {
+        match self {
+            Subtree::Node(Node::Leaf { points, .. }, _) => points,
+            Subtree::Point(point) => std::slice::from_ref(*point),
+            _ => unreachable!("this function should only be used on leaves and points"),
+        }
+    }
+}
+
+/// How much every point (and subtree) of a tree contributes to a pair count.
+trait PairWeights<T: Float, const D: usize> {
+    type Weight: Copy + AddAssign + Mul<Output = Self::Weight> + Sub<Output = Self::Weight>;
+
+    fn zero() -> Self::Weight;
+
+    fn subtree(&self, subtree: &Subtree<'_, T, D>) -> Self::Weight;
+
+    fn point(&self, point: &Point<T, D>) -> Self::Weight;
+}
+
+/// Every point contributes one.
+struct Unweighted;
+
+impl<T: Float, const D: usize> PairWeights<T, D> for Unweighted {
+    type Weight = u64;
+
+    fn zero() -> u64 {
+        0
+    }
+
+    fn subtree(&self, subtree: &Subtree<'_, T, D>) -> u64 {
+        subtree.num_points() as u64
+    }
+
+    fn point(&self, _point: &Point<T, D>) -> u64 {
+        1
+    }
+}
+
+/// Every point contributes its weight, with the total weight of every node precomputed.
+struct Weighted<'w, T: Float, const D: usize> {
+    weights: &'w [T],
+    node_weights: Vec<T>,
+    start: *const [NotNan<T>; D],
+}
+
+impl<'w, T: Float + Debug, const D: usize> Weighted<'w, T, D> {
+    fn new<M: Metric<T, D>>(tree: &Tree<'_, T, D, M>, weights: &'w [T]) -> Self {
+        let mut weighted = Weighted {
+            weights,
+            node_weights: vec![T::zero(); tree.nodes.len() + 1],
+            start: tree.start(),
+        };
+        weighted.sum_node(&tree.nodes, &tree.root_node, tree.nodes.len());
+        weighted
+    }
+
+    /// Records the total weight of `node` (and of all of its descendants), returning it.
+    fn sum_node(&mut self, nodes: &[Node<T, D>], node: &Node<T, D>, index: usize) -> T {
+        let total = match node {
+            Node::Stem {
+                point, left, right, ..
+            } => {
+                // safety: indices are valid by construction, with the atomic lock on Vec<Node>
+                let (left_node, right_node) =
+                    unsafe { (nodes.get_unchecked(*left), nodes.get_unchecked(*right)) };
+                self.point(point)
+                    + self.sum_node(nodes, left_node, *left)
+                    + self.sum_node(nodes, right_node, *right)
+            }
+            Node::Leaf { points, .. } => points
+                .iter()
+                .fold(T::zero(), |acc, point| acc + self.point(point)),
+        };
+        self.node_weights[index] = total;
+        total
+    }
+}
+
+impl<'w, T: Float, const D: usize> PairWeights<T, D> for Weighted<'w, T, D> {
+    type Weight = T;
+
+    fn zero() -> T {
+        T::zero()
+    }
+
+    fn subtree(&self, subtree: &Subtree<'_, T, D>) -> T {
+        match subtree {
+            Subtree::Node(_, index) => self.node_weights[*index],
+            Subtree::Point(point) => self.point(point),
+        }
+    }
+
+    fn point(&self, point: &Point<T, D>) -> T {
+        self.weights[point.index(self.start) as usize]
+    }
+}
+
+struct DualTree<'a, T: Float, const D: usize, W: PairWeights<T, D>, M: Metric<T, D>> {
+    trees: (&'a [Node<T, D>], &'a [Node<T, D>]),
+    weights: (&'a W, &'a W),
+    edges2: &'a [T],
+    boxsize: Option<&'a [NotNan<T>; D]>,
+    metric: &'a M,
+}
+
+impl<'a, T: Float, const D: usize, W: PairWeights<T, D>, M: Metric<T, D>> DualTree<'a, T, D, W, M> {
+    /// Adds the pairs between subtrees `a` and `b` to the `cumulative` counts of the
+    /// undecided bin edges `lo..hi`. All pairs are known to be closer than the edges
+    /// after `hi` and no farther than those before `lo`, and have been counted accordingly.
+    fn traverse(
+        &self,
+        a: Subtree<'a, T, D>,
+        b: Subtree<'a, T, D>,
+        mut lo: usize,
+        mut hi: usize,
+        cumulative: &mut [W::Weight],
+    ) {
+        let (lower_a, upper_a) = a.bounds();
+        let (lower_b, upper_b) = b.bounds();
+        let (min_dist2, max_dist2) =
+            self.metric
+                .dist_between_spaces(lower_a, upper_a, lower_b, upper_b, self.boxsize);
+
+        // No pair is closer than these edges
+        while lo < hi && self.edges2[lo] <= min_dist2 {
+            lo += 1;
+        }
+
+        // Every pair is closer than these edges
+        if lo < hi && self.edges2[hi - 1] > max_dist2 {
+            let weight = self.weights.0.subtree(&a) * self.weights.1.subtree(&b);
+            while lo < hi && self.edges2[hi - 1] > max_dist2 {
+                hi -= 1;
+                cumulative[hi] += weight;
+            }
+        }
+
+        if lo == hi {
+            return;
+        }
+
+        match (a.children(self.trees.0), b.children(self.trees.1)) {
+            (None, None) => self.check_pairs(a, b, lo, hi, cumulative),
+            (Some(children), None) => {
+                for child in children {
+                    self.traverse(child, b, lo, hi, cumulative);
+                }
+            }
+            (None, Some(children)) => {
+                for child in children {
+                    self.traverse(a, child, lo, hi, cumulative);
+                }
+            }
+            (Some(a_children), Some(b_children)) => {
+                // Split the larger subtree
+                if a.num_points() >= b.num_points() {
+                    for child in a_children {
+                        self.traverse(child, b, lo, hi, cumulative);
+                    }
+                } else {
+                    for child in b_children {
+                        self.traverse(a, child, lo, hi, cumulative);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Checks every pair between two leaves (or points) individually.
+    fn check_pairs(
+        &self,
+        a: Subtree<'a, T, D>,
+        b: Subtree<'a, T, D>,
+        lo: usize,
+        hi: usize,
+        cumulative: &mut [W::Weight],
+    ) {
+        for point_a in a.points() {
+            for point_b in b.points() {
+                let dist2 = match self.boxsize {
+                    Some(boxsize) => {
+                        self.metric
+                            .dist_periodic(point_a.position(), point_b.position(), boxsize)
+                    }
+                    None => self.metric.dist(point_a.position(), point_b.position()),
+                };
+
+                // First edge this pair is closer than
+                let first = lo + self.edges2[lo..hi].partition_point(|edge2| *edge2 <= dist2);
+                if first < hi {
+                    let weight = self.weights.0.point(point_a) * self.weights.1.point(point_b);
+                    for count in &mut cumulative[first..hi] {
+                        *count += weight;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{metric::Metric, point::Float};
+use likely_stable::unlikely;
+use ordered_float::NotNan;
+
+/// Returns every image of the periodic box as a bitmask of the dimensions along which the
+/// query is shifted, in increasing order. Only the periodic dimensions (those with a finite
+/// `boxsize`) are shifted, so there are `2^P - 1` images for `P` periodic dimensions.
+pub(crate) fn images<T: Float, const D: usize>(
+    boxsize: &[NotNan<T>; D],
+) -> impl Iterator<Item = usize> {
+    let periodic = boxsize
+        .iter()
+        .enumerate()
+        .filter(|(_, side)| side.is_finite())
+        .fold(0_usize, |mask, (dim, _)| mask | (1 << dim));
+
+    // Enumerates the nonempty submasks of `periodic`
+    let mut image = 0_usize;
+    std::iter::from_fn(move || {
+        image = (image | !periodic).wrapping_add(1) & periodic;
+        (image != 0).then_some(image)
+    })
+}
+
+/// Wraps the query point `query` into the periodic box of size `boxsize` whose lower corner is
+/// `origin`, along every periodic dimension. Components that already lie within the box
+/// (including on its upper side) are returned unchanged, so that wrapping never perturbs the
+/// distances of queries within the box.
+pub(crate) fn wrap<T: Float, const D: usize>(
+    query: &[NotNan<T>; D],
+    boxsize: &[NotNan<T>; D],
+    origin: &[NotNan<T>; D],
+) -> [NotNan<T>; D] {
+    let mut wrapped = *query;
+    for ((component, side), lower) in wrapped.iter_mut().zip(boxsize).zip(origin) {
+        let offset = **component - **lower;
+        if side.is_infinite() || (!offset.is_sign_negative() && offset <= **side) {
+            continue;
+        }
+
+        // Rounding may place the wrapped offset on (or just beyond) either side of the box,
+        // which are the same point
+        let mut wrapped_offset = offset - (offset / **side).floor() * **side;
+        if wrapped_offset.is_sign_negative() || wrapped_offset >= **side {
+            wrapped_offset = T::zero();
+        }
+        *component = *lower + wrapped_offset;
+    }
+    wrapped
+}
+
+/// Given a query point `query` in a periodic box of size `boxsize` whose lower corner is
+/// `origin`, returns all of the (non-real) images of `query` whose associated side, edge,
+/// vertex (or other higher dimensional equivalent) of the box is closer than the reduced
+/// distance `dist2` of the `metric` to the query.
+///
+/// Only the closest image along every dimension is considered, i.e. the query is shifted
+/// by `+boxsize` if it is in the lower half of the box and by `-boxsize` if it is in the
+/// upper half. This is sufficient so long as `dist2` does not exceed the reduced distance
+/// of half of the boxsize along any dimension.
+pub(crate) fn images_within<T: Float, const D: usize, M: Metric<T, D>>(
+    metric: &M,
+    query: &[NotNan<T>; D],
+    boxsize: &[NotNan<T>; D],
+    origin: &[NotNan<T>; D],
+    dist2: T,
+) -> Vec<[NotNan<T>; D]> {
+    // Find closest dist2 to every side
+    let mut closest_side_dist2 = [T::zero(); D];
+    for (side, side_dist2) in closest_side_dist2.iter_mut().enumerate() {
+        // Do a single index here. This is equal to distance to lower side
+        // safety: made safe by const generic
+        let query_component = &unsafe { query.get_unchecked(side) - origin.get_unchecked(side) };
+
+        // Get distance to upper half
+        // safety: made safe by const generic
+        let upper = unsafe { boxsize.get_unchecked(side) } - query_component;
+
+        // !negative includes zero. The query may be negative along nonperiodic
+        // dimensions, whose upper side is infinite.
+        debug_assert!(!upper.is_sign_negative());
+        debug_assert!(upper.is_infinite() || !query_component.is_sign_negative());
+
+        // Choose lesser of two and then take its reduced distance
+        *side_dist2 = metric.axis_dist(side, *upper.min(*query_component));
+    }
+
+    // Find which images we need to check
+    let mut images_to_check = Vec::with_capacity(2_usize.pow(D as u32) - 1);
+    for image in images(boxsize) {
+        // Closest image in the form of bool array
+        let closest_image = (0..D as u32).map(|idx| ((image / 2_usize.pow(idx)) % 2) == 1);
+
+        // Find distance to corresponding side, edge, vertex or other higher dimensional equivalent
+        let dist_to_side_edge_or_other: T = closest_image
+            .clone()
+            .zip(closest_side_dist2.iter())
+            .filter_map(|(flag, side_dist2)| flag.then_some(*side_dist2))
+            .fold(T::zero(), |acc, x| metric.accumulate(acc, x));
+
+        // INTRINSICS: in any reasonably sized kdtree, most points will not be near the edge
+        if unlikely(dist_to_side_edge_or_other < dist2) {
+            let mut image_to_check = *query;
+
+            for (idx, flag) in closest_image.enumerate() {
+                // If moving image along this dimension
+                if flag {
+                    // safety: made safe by const generic
+                    unsafe {
+                        let query_component: &NotNan<T> = query.get_unchecked(idx);
+                        let boxsize_component = boxsize.get_unchecked(idx);
+
+                        if query_component - origin.get_unchecked(idx)
+                            < boxsize_component / T::from(2.0).unwrap()
+                        {
+                            // Add if in lower half of box
+                            *image_to_check.get_unchecked_mut(idx) =
+                                query_component + boxsize_component
+                        } else {
+                            // Subtract if in upper half of box
+                            *image_to_check.get_unchecked_mut(idx) =
+                                query_component - boxsize_component
+                        }
+                    }
+                }
+            }
+
+            images_to_check.push(image_to_check);
+        }
+    }
+
+    images_to_check
+}
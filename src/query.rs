@@ -2,31 +2,64 @@ use std::fmt::Debug;
 
 use crate::{
     distance::*,
+    metric::Metric,
+    periodic::images,
     point::{Float, Point},
-    utils::{check_point_return, process_result, FnntwResult, QueryResult},
+    query_k::container::Container,
+    utils::{check_epsilon_return, FnntwResult, QueryResult},
     Node, Tree,
 };
 use likely_stable::unlikely;
 use ordered_float::NotNan;
 
-impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
+impl<'t, T: Float + Debug, const D: usize, M: Metric<T, D>> Tree<'t, T, D, M> {
     pub fn query_nearest<'q>(&'q self, query: &[T; D]) -> FnntwResult<QueryResult<'q, T, D>, T> {
         // Check for valid query point
-        let query: &[NotNan<T>; D] = check_point_return(query)?;
+        let query: &[NotNan<T>; D] = &self.check_query(query)?;
 
         if let Some(ref boxsize) = self.boxsize {
             // Periodic query
-            Ok(process_result::<T, D>(
-                self.query_nearest_periodic(query, boxsize),
-            ))
+            Ok(self.process_result(self.query_nearest_periodic(query, boxsize)))
         } else {
             // Non periodic query
-            Ok(process_result::<T, D>(
-                self.query_nearest_nonperiodic(query),
-            ))
+            Ok(self.process_result(self.query_nearest_nonperiodic(query)))
         }
     }
 
+    /// Same as [`Tree::query_nearest`], except that the neighbor returned is only guaranteed to
+    /// be within a factor of `1 + epsilon` of the distance to the true nearest neighbor. Subtrees
+    /// are only visited if their distance times `1 + epsilon` is closer than the current best,
+    /// so larger `epsilon` visit fewer subtrees.
+    pub fn query_nearest_approx<'q>(
+        &'q self,
+        query: &[T; D],
+        epsilon: T,
+    ) -> FnntwResult<QueryResult<'q, T, D>, T> {
+        // Check for valid query point and epsilon
+        let query: &[NotNan<T>; D] = &self.check_query(query)?;
+        let prune_scale = check_epsilon_return(epsilon, &self.metric)?;
+
+        // Find the single approximate neighbor with the approximate kNN search
+        let mut container = Container::new(1);
+        let mut points_to_check = Vec::with_capacity(self.height_hint);
+        self.fill_nearest_k_approx(query, &mut container, &mut points_to_check, prune_scale);
+        let (best_dist2, best_nn) = container.best();
+
+        Ok(self.process_result((
+            best_dist2,
+            best_nn.index(self.start()),
+            #[cfg(not(feature = "no-position"))]
+            best_nn.position(),
+        )))
+    }
+
+    /// Converts the reduced distance of a result into the distance returned by queries.
+    #[inline(always)]
+    fn process_result<'q>(&self, mut result: QueryResult<'q, T, D>) -> QueryResult<'q, T, D> {
+        result.0 = self.metric.process(result.0);
+        result
+    }
+
     /// Given a query point `query`, query the tree and return point's nearest neighbor.
     /// The value returned is (`distance_to_neighbor: T`, `neighbor_index: u64`, `neighbor_position: &'t [NotNan<T>; D]`).
     fn query_nearest_nonperiodic<'q>(&'q self, query: &[NotNan<T>; D]) -> QueryResult<'q, T, D> {
@@ -75,24 +108,26 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
         for side in 0..D {
             // Do a single index here. This is equal to distance to lower side
             // safety: made safe with const generic
-            let query_component = unsafe { query.get_unchecked(side) };
+            let query_component =
+                &unsafe { query.get_unchecked(side) - self.origin.get_unchecked(side) };
 
             // Get distance to upper half
             // safety: made safe with const gneric
             let upper = unsafe { boxsize.get_unchecked(side) } - query_component;
 
-            // !negative includes zero
+            // !negative includes zero. The query may be negative along nonperiodic
+            // dimensions, whose upper side is infinite.
             debug_assert!(!upper.is_sign_negative());
-            debug_assert!(!query_component.is_sign_negative());
+            debug_assert!(upper.is_infinite() || !query_component.is_sign_negative());
 
-            // Choose lesser of two and then square
-            closest_side_dist2[side] = upper.min(*query_component).powi(2);
+            // Choose lesser of two and then take its reduced distance
+            closest_side_dist2[side] = self.metric.axis_dist(side, *upper.min(*query_component));
         }
 
         // Find which images we need to check.
         // Initialize vector with real image (which we will remove later)
         let mut images_to_check = Vec::with_capacity(2_usize.pow(D as u32) - 1);
-        for image in 1..2_usize.pow(D as u32) {
+        for image in images(boxsize) {
             // Closest image in the form of bool array
             let closest_image = (0..D as u32).map(|idx| ((image / 2_usize.pow(idx)) % 2) == 1);
 
@@ -109,7 +144,7 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
                         None
                     }
                 })
-                .fold(T::zero(), |acc, x| acc + *x);
+                .fold(T::zero(), |acc, x| self.metric.accumulate(acc, *x));
 
             // INTRINSICS: in any reasonably sized kdtree, most points will not be near the edge
             if unlikely(dist_to_side_edge_or_other < best_dist2) {
@@ -128,7 +163,9 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
 
                         // safety: made safe with const generic
                         unsafe {
-                            if *query_component < boxsize_component / T::from(2.0).unwrap() {
+                            if query_component - self.origin.get_unchecked(idx)
+                                < boxsize_component / T::from(2.0).unwrap()
+                            {
                                 // Add if in lower half of box
                                 *image_to_check.get_unchecked_mut(idx) =
                                     query_component + boxsize_component
@@ -231,6 +268,7 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
         // Check all points in leaf
         for candidate in leaf_points.into_iter() {
             new_best(
+                &self.metric,
                 query,
                 candidate,
                 current_best_dist_sq,
@@ -273,7 +311,8 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
                         let (sibling_lower, sibling_upper) =
                             unsafe { self.nodes.get_unchecked(*left) }.get_bounds();
                         let dist_sq_to_space =
-                            calc_dist_sq_to_space(query, sibling_lower, sibling_upper);
+                            self.metric
+                                .dist_to_space(query, sibling_lower, sibling_upper);
                         // if dist_sq_to_space < *current_best_dist_sq {
                         points_to_check.push((left, point, dist_sq_to_space));
                         // }
@@ -286,7 +325,8 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
                         let (sibling_lower, sibling_upper) =
                             unsafe { self.nodes.get_unchecked(*right) }.get_bounds();
                         let dist_sq_to_space =
-                            calc_dist_sq_to_space(query, sibling_lower, sibling_upper);
+                            self.metric
+                                .dist_to_space(query, sibling_lower, sibling_upper);
                         // if dist_sq_to_space < *current_best_dist_sq {
                         points_to_check.push((right, point, dist_sq_to_space));
                         // }
@@ -336,6 +376,12 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
         'i: 'o,
         't: 'i,
     {
-        new_best(query, stem, current_best_dist_sq, current_best_neighbor);
+        new_best(
+            &self.metric,
+            query,
+            stem,
+            current_best_dist_sq,
+            current_best_neighbor,
+        );
     }
 }
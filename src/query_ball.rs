@@ -0,0 +1,172 @@
+use std::fmt::Debug;
+
+use crate::{
+    metric::Metric,
+    periodic::images_within,
+    point::{Float, Point},
+    utils::{check_radius_return, FnntwResult, QueryBallResult},
+    Node, Tree,
+};
+use ordered_float::NotNan;
+
+pub mod parallel;
+
+impl<'t, T: Float + Debug, const D: usize, M: Metric<T, D>> Tree<'t, T, D, M> {
+    /// Given a query point `query`, query the tree and return all points within a distance
+    /// `radius` (inclusive) of the query, sorted by distance. If the tree has a boxsize,
+    /// the minimum image distance is used, in which case `radius` must be smaller than
+    /// half of the boxsize.
+    ///
+    /// Note that `radius` is always a distance of the tree's metric (e.g. the not squared
+    /// euclidean distance), even when the `sqrt-dist2` feature is not enabled and reduced
+    /// (e.g. squared) distances are returned.
+    pub fn query_ball_point<'q>(
+        &'q self,
+        query: &[T; D],
+        radius: T,
+    ) -> FnntwResult<QueryBallResult<'t, T, D>, T> {
+        // Check for valid query point
+        let query: &[NotNan<T>; D] = &self.check_query(query)?;
+
+        self.query_ball_point_checked(query, radius)
+    }
+
+    /// Finds all points within `radius` of an already checked `query`, checking only the
+    /// radius.
+    pub(crate) fn query_ball_point_checked(
+        &self,
+        query: &[NotNan<T>; D],
+        radius: T,
+    ) -> FnntwResult<QueryBallResult<'t, T, D>, T> {
+        let radius2 = check_radius_return(radius, self.boxsize.as_ref(), &self.metric)?;
+
+        let mut neighbors = Vec::new();
+        let mut nodes_to_check = Vec::with_capacity(self.height_hint);
+        self.query_ball_point_into(query, radius2, &mut neighbors, &mut nodes_to_check);
+
+        Ok(self.ball_result(&mut neighbors))
+    }
+
+    /// Pushes all neighbors within `radius2` of an already checked `query` onto `neighbors`,
+    /// dispatching to the periodic query if the tree has a boxsize.
+    pub(crate) fn query_ball_point_into<'i>(
+        &'i self,
+        query: &[NotNan<T>; D],
+        radius2: T,
+        neighbors: &mut Vec<(T, &'i Point<T, D>)>,
+        nodes_to_check: &mut Vec<&'i Node<T, D>>,
+    ) {
+        if let Some(ref boxsize) = self.boxsize {
+            // Periodic query
+            self.query_ball_point_periodic(query, radius2, boxsize, neighbors, nodes_to_check);
+        } else {
+            // Nonperiodic query
+            self.check_ball(query, radius2, neighbors, nodes_to_check);
+        }
+    }
+
+    fn query_ball_point_periodic<'i>(
+        &'i self,
+        query: &[NotNan<T>; D],
+        radius2: T,
+        boxsize: &[NotNan<T>; D],
+        neighbors: &mut Vec<(T, &'i Point<T, D>)>,
+        nodes_to_check: &mut Vec<&'i Node<T, D>>,
+    ) {
+        // First get real image result
+        self.check_ball(query, radius2, neighbors, nodes_to_check);
+
+        // Then check all images within the radius. Since the radius is smaller
+        // than half the boxsize, no point can be found in more than one image.
+        for image in images_within(&self.metric, query, boxsize, &self.origin, radius2) {
+            self.check_ball(&image, radius2, neighbors, nodes_to_check);
+        }
+    }
+
+    /// Traverses the tree, pushing every point within `radius2` of `query` onto `neighbors`.
+    /// Any stem or leaf whose bounding box is farther than `radius2` is pruned.
+    pub(crate) fn check_ball<'i>(
+        &'i self,
+        query: &[NotNan<T>; D],
+        radius2: T,
+        neighbors: &mut Vec<(T, &'i Point<T, D>)>,
+        nodes_to_check: &mut Vec<&'i Node<T, D>>,
+    ) {
+        // Check the root node's space before traversing
+        let (lower, upper) = self.root_node.get_bounds();
+        if self.metric.dist_to_space(query, lower, upper) <= radius2 {
+            nodes_to_check.push(&self.root_node);
+        }
+
+        while let Some(node) = nodes_to_check.pop() {
+            match node {
+                Node::Stem {
+                    point, left, right, ..
+                } => {
+                    // The stem point is not in either child
+                    check_ball_point(&self.metric, query, point, radius2, neighbors);
+
+                    for child in [left, right] {
+                        // safety: indices are valid by construction, with the atomic lock on Vec<Node>
+                        let child = unsafe { self.nodes.get_unchecked(*child) };
+                        let (lower, upper) = child.get_bounds();
+                        if self.metric.dist_to_space(query, lower, upper) <= radius2 {
+                            nodes_to_check.push(child);
+                        }
+                    }
+                }
+                Node::Leaf { points, .. } => {
+                    for candidate in points {
+                        check_ball_point(&self.metric, query, candidate, radius2, neighbors);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sorts the neighbors found by a ball query and processes them into a [`QueryBallResult`].
+    /// This empties `neighbors` so that its allocation may be reused.
+    pub(crate) fn ball_result(
+        &self,
+        neighbors: &mut Vec<(T, &Point<T, D>)>,
+    ) -> QueryBallResult<'t, T, D> {
+        let mut neighbors: Vec<(T, u64, &Point<T, D>)> = neighbors
+            .drain(..)
+            .map(|(dist2, neighbor)| (dist2, neighbor.index(self.start()), neighbor))
+            .collect();
+
+        // Sort by distance, breaking ties with the index
+        neighbors.sort_unstable_by(|a, b| {
+            a.0.partial_cmp(&b.0)
+                .expect("distances are never nan")
+                .then(a.1.cmp(&b.1))
+        });
+
+        let mut result: QueryBallResult<'t, T, D> = (
+            Vec::with_capacity(neighbors.len()),
+            Vec::with_capacity(neighbors.len()),
+            #[cfg(not(feature = "no-position"))]
+            Vec::with_capacity(neighbors.len()),
+        );
+        for (dist2, index, _neighbor) in neighbors {
+            result.0.push(self.metric.process(dist2));
+            result.1.push(index);
+            #[cfg(not(feature = "no-position"))]
+            result.2.push(*_neighbor.position());
+        }
+        result
+    }
+}
+
+fn check_ball_point<'i, T: Float, const D: usize, M: Metric<T, D>>(
+    metric: &M,
+    query: &[NotNan<T>; D],
+    candidate: &'i Point<T, D>,
+    radius2: T,
+    neighbors: &mut Vec<(T, &'i Point<T, D>)>,
+) {
+    let dist2 = metric.dist(query, candidate.position());
+    if dist2 <= radius2 {
+        neighbors.push((dist2, candidate));
+    }
+}
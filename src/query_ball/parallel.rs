@@ -0,0 +1,138 @@
+#![cfg(feature = "parallel")]
+
+use std::fmt::Debug;
+
+use crate::{
+    metric::Metric,
+    point::Float,
+    utils::{check_radius_return, FnntwError, FnntwResult, QueryBallCsrResult, QueryBallResult},
+    Tree,
+};
+use ordered_float::NotNan;
+
+impl<'t, T: Float + Debug, const D: usize, M: Metric<T, D>> Tree<'t, T, D, M> {
+    /// Performs a ball query with the same `radius` for every query in `queries` in parallel.
+    /// The result is returned in compressed sparse row (CSR) format, i.e. as
+    /// (`offsets`, `distances`, `indices`), where the neighbors of `queries[i]` are found at
+    /// `offsets[i]..offsets[i + 1]`. Within each query, neighbors are sorted by distance.
+    pub fn query_ball_point_parallel<'q>(
+        &'q self,
+        queries: &'q [[T; D]],
+        radius: T,
+    ) -> FnntwResult<QueryBallCsrResult<'t, T, D>, T> {
+        // Check for valid radius once for all queries
+        let radius2 = check_radius_return(radius, self.boxsize.as_ref(), &self.metric)?;
+
+        self.query_ball_point_parallel_by(queries, |_| Ok(radius2))
+    }
+
+    /// Performs a ball query with a different radius `radii[i]` for every query `queries[i]`
+    /// in parallel. The result is in the same CSR format as [`Tree::query_ball_point_parallel`].
+    pub fn query_ball_point_parallel_radii<'q>(
+        &'q self,
+        queries: &'q [[T; D]],
+        radii: &'q [T],
+    ) -> FnntwResult<QueryBallCsrResult<'t, T, D>, T> {
+        if radii.len() != queries.len() {
+            return Err(FnntwError::RadiiLengthMismatch);
+        }
+
+        self.query_ball_point_parallel_by(queries, |query_index| {
+            // safety: lengths were just checked
+            check_radius_return(
+                *unsafe { radii.get_unchecked(query_index) },
+                self.boxsize.as_ref(),
+                &self.metric,
+            )
+        })
+    }
+
+    fn query_ball_point_parallel_by<'q>(
+        &'q self,
+        queries: &'q [[T; D]],
+        radius2: impl Fn(usize) -> FnntwResult<T, T> + Sync,
+    ) -> FnntwResult<QueryBallCsrResult<'t, T, D>, T> {
+        use rayon::prelude::{
+            IndexedParallelIterator, IntoParallelIterator, ParallelIterator, ParallelSlice,
+        };
+
+        // Query every point, reusing the per-thread buffers
+        let results: Vec<QueryBallResult<'t, T, D>> = queries
+            .into_par_iter()
+            .enumerate()
+            .map_init(
+                || (Vec::new(), Vec::with_capacity(self.height_hint)),
+                |(neighbors, nodes_to_check), (query_index, query)| -> FnntwResult<_, T> {
+                    // Check for valid query point and radius
+                    let query: &[NotNan<T>; D] = &self.check_query(query)?;
+                    let radius2 = radius2(query_index)?;
+
+                    self.query_ball_point_into(query, radius2, neighbors, nodes_to_check);
+
+                    Ok(self.ball_result(neighbors))
+                },
+            )
+            .collect::<FnntwResult<_, T>>()?;
+
+        // Compute the offsets of every query's neighbors
+        let mut offsets = Vec::with_capacity(queries.len() + 1);
+        offsets.push(0);
+        for result in &results {
+            offsets.push(offsets.last().unwrap() + result.0.len());
+        }
+        let total = *offsets.last().unwrap();
+
+        let mut distances = Vec::with_capacity(total);
+        let mut indices = Vec::with_capacity(total);
+        #[cfg(not(feature = "no-position"))]
+        let mut positions = Vec::with_capacity(total);
+        let dist_ptr_usize = distances.as_mut_ptr() as usize;
+        let idx_ptr_usize = indices.as_mut_ptr() as usize;
+        #[cfg(not(feature = "no-position"))]
+        let pos_ptr_usize = positions.as_mut_ptr() as usize;
+
+        // Write every query's neighbors into its row
+        results
+            .into_par_iter()
+            .zip(offsets.par_windows(2))
+            .for_each(|(result, offset)| {
+                // safety: the rows are disjoint and all lie within the allocated capacity
+                unsafe {
+                    let row = offset[0];
+                    let len = offset[1] - offset[0];
+                    std::ptr::copy_nonoverlapping(
+                        result.0.as_ptr(),
+                        (dist_ptr_usize as *mut T).add(row),
+                        len,
+                    );
+                    std::ptr::copy_nonoverlapping(
+                        result.1.as_ptr(),
+                        (idx_ptr_usize as *mut u64).add(row),
+                        len,
+                    );
+                    #[cfg(not(feature = "no-position"))]
+                    std::ptr::copy_nonoverlapping(
+                        result.2.as_ptr(),
+                        (pos_ptr_usize as *mut [NotNan<T>; D]).add(row),
+                        len,
+                    );
+                }
+            });
+
+        // safety: every element up to total was just written
+        unsafe {
+            distances.set_len(total);
+            indices.set_len(total);
+            #[cfg(not(feature = "no-position"))]
+            positions.set_len(total);
+        }
+
+        Ok((
+            offsets,
+            distances,
+            indices,
+            #[cfg(not(feature = "no-position"))]
+            positions,
+        ))
+    }
+}
@@ -0,0 +1,115 @@
+use std::fmt::Debug;
+
+use crate::{
+    metric::Metric,
+    periodic::images_within,
+    point::{Float, Point},
+    utils::{check_radius_return, FnntwResult},
+    Node, Tree,
+};
+use ordered_float::NotNan;
+
+pub mod parallel;
+
+impl<'t, T: Float + Debug, const D: usize, M: Metric<T, D>> Tree<'t, T, D, M> {
+    /// Given a query point `query`, query the tree and return the number of points within a
+    /// distance `radius` (inclusive) of the query, without materializing the neighbors. If the
+    /// tree has a boxsize, the minimum image distance is used, in which case `radius` must be
+    /// smaller than half of the boxsize.
+    ///
+    /// Note that `radius` is always a distance of the tree's metric (e.g. the not squared
+    /// euclidean distance), even when the `sqrt-dist2` feature is not enabled.
+    pub fn count_within(&self, query: &[T; D], radius: T) -> FnntwResult<u64, T> {
+        // Check for valid query point and radius
+        let query: &[NotNan<T>; D] = &self.check_query(query)?;
+        let radius2 = check_radius_return(radius, self.boxsize.as_ref(), &self.metric)?;
+
+        let mut nodes_to_check = Vec::with_capacity(self.height_hint);
+        Ok(self.count_within_into(query, radius2, &mut nodes_to_check))
+    }
+
+    /// Counts all points within `radius2` of an already checked `query`, dispatching to the
+    /// periodic count if the tree has a boxsize.
+    pub(crate) fn count_within_into<'i>(
+        &'i self,
+        query: &[NotNan<T>; D],
+        radius2: T,
+        nodes_to_check: &mut Vec<&'i Node<T, D>>,
+    ) -> u64 {
+        if let Some(ref boxsize) = self.boxsize {
+            // Periodic count. First count real image, then all images within the radius.
+            // Since the radius is smaller than half the boxsize, no point can be counted
+            // in more than one image.
+            let mut count = self.count_ball(query, radius2, nodes_to_check);
+            for image in images_within(&self.metric, query, boxsize, &self.origin, radius2) {
+                count += self.count_ball(&image, radius2, nodes_to_check);
+            }
+            count
+        } else {
+            // Nonperiodic count
+            self.count_ball(query, radius2, nodes_to_check)
+        }
+    }
+
+    /// Traverses the tree, counting every point within `radius2` of `query`. Any stem or leaf
+    /// whose bounding box is farther than `radius2` is pruned, and any whose bounding box lies
+    /// entirely within `radius2` contributes all of its points without being visited.
+    pub(crate) fn count_ball<'i>(
+        &'i self,
+        query: &[NotNan<T>; D],
+        radius2: T,
+        nodes_to_check: &mut Vec<&'i Node<T, D>>,
+    ) -> u64 {
+        let mut count = 0;
+
+        // Check the root node's space before traversing
+        let (lower, upper) = self.root_node.get_bounds();
+        if self.metric.dist_to_space(query, lower, upper) <= radius2 {
+            nodes_to_check.push(&self.root_node);
+        }
+
+        while let Some(node) = nodes_to_check.pop() {
+            // Whole node is within the ball
+            let (lower, upper) = node.get_bounds();
+            if self.metric.max_dist_to_space(query, lower, upper) <= radius2 {
+                count += node.num_points() as u64;
+                continue;
+            }
+
+            match node {
+                Node::Stem {
+                    point, left, right, ..
+                } => {
+                    // The stem point is not in either child
+                    count += within(&self.metric, query, point, radius2) as u64;
+
+                    for child in [left, right] {
+                        // safety: indices are valid by construction, with the atomic lock on Vec<Node>
+                        let child = unsafe { self.nodes.get_unchecked(*child) };
+                        let (lower, upper) = child.get_bounds();
+                        if self.metric.dist_to_space(query, lower, upper) <= radius2 {
+                            nodes_to_check.push(child);
+                        }
+                    }
+                }
+                Node::Leaf { points, .. } => {
+                    count += points
+                        .iter()
+                        .filter(|candidate| within(&self.metric, query, candidate, radius2))
+                        .count() as u64;
+                }
+            }
+        }
+
+        count
+    }
+}
+
+fn within<T: Float, const D: usize, M: Metric<T, D>>(
+    metric: &M,
+    query: &[NotNan<T>; D],
+    candidate: &Point<T, D>,
+    radius2: T,
+) -> bool {
+    metric.dist(query, candidate.position()) <= radius2
+}
@@ -0,0 +1,40 @@
+#![cfg(feature = "parallel")]
+
+use std::fmt::Debug;
+
+use crate::{
+    metric::Metric,
+    point::Float,
+    utils::{check_radius_return, FnntwResult},
+    Tree,
+};
+use ordered_float::NotNan;
+
+impl<'t, T: Float + Debug, const D: usize, M: Metric<T, D>> Tree<'t, T, D, M> {
+    /// Counts the number of points within `radius` of every query in `queries` in parallel.
+    /// The count of `queries[i]` is found at index `i` of the result.
+    pub fn count_within_parallel<'q>(
+        &'q self,
+        queries: &'q [[T; D]],
+        radius: T,
+    ) -> FnntwResult<Vec<u64>, T> {
+        use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+
+        // Check for valid radius once for all queries
+        let radius2 = check_radius_return(radius, self.boxsize.as_ref(), &self.metric)?;
+
+        // Count for every point, reusing the per-thread buffer
+        queries
+            .into_par_iter()
+            .map_init(
+                || Vec::with_capacity(self.height_hint),
+                |nodes_to_check, query| -> FnntwResult<_, T> {
+                    // Check for valid query point
+                    let query: &[NotNan<T>; D] = &self.check_query(query)?;
+
+                    Ok(self.count_within_into(query, radius2, nodes_to_check))
+                },
+            )
+            .collect()
+    }
+}
@@ -2,35 +2,92 @@ use std::fmt::Debug;
 
 use crate::{
     distance::*,
+    metric::Metric,
+    periodic::{images, images_within},
     point::{Float, Point},
-    utils::{check_point_return, FnntwResult, QueryKResult},
+    utils::{FnntwResult, QueryKResult},
     Node, Tree,
 };
 use ordered_float::NotNan;
 
+pub mod all;
+pub mod approx;
+pub mod bounded;
 pub mod container;
 pub mod container_axis;
+pub mod exclude;
 pub mod parallel;
 pub mod parallel_axis;
+pub mod parallel_ks;
 pub mod parallel_with;
+pub mod ranks;
 // pub mod with;
 use container::Container;
 
-impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
+impl<'t, T: Float + Debug, const D: usize, M: Metric<T, D>> Tree<'t, T, D, M> {
     pub fn query_nearest_k<'q>(
         &'q self,
         query: &'q [T; D],
         k: usize,
     ) -> FnntwResult<QueryKResult<'t, T, D>, T> {
         // Check for valid query point
-        let query: &[NotNan<T>; D] = check_point_return(query)?;
+        let query: &[NotNan<T>; D] = &self.check_query(query)?;
 
+        Ok(self.query_nearest_k_checked(query, k))
+    }
+
+    /// Finds the `k` nearest neighbors of an already checked `query`, dispatching to the
+    /// periodic query if the tree has a boxsize.
+    pub(crate) fn query_nearest_k_checked<'q>(
+        &'q self,
+        query: &'q [NotNan<T>; D],
+        k: usize,
+    ) -> QueryKResult<'t, T, D>
+    where
+        't: 'q,
+    {
         if let Some(ref boxsize) = self.boxsize {
             // Periodic query
-            Ok(self.query_nearest_k_periodic(query, k, boxsize))
+            self.query_nearest_k_periodic(query, k, boxsize)
         } else {
             // Nonperiodic query
-            Ok(self.query_nearest_k_nonperiodic(query, k))
+            self.query_nearest_k_nonperiodic(query, k)
+        }
+    }
+
+    /// Fills an empty `container` with the nearest neighbors of an already checked `query`,
+    /// dispatching to the periodic query if the tree has a boxsize. This allows the container
+    /// and `points_to_check` to be reused across queries.
+    pub(crate) fn fill_nearest_k<'i>(
+        &'i self,
+        query: &[NotNan<T>; D],
+        container: &mut Container<'i, T, D>,
+        points_to_check: &mut Vec<(&'i usize, &'i Point<T, D>, T)>,
+    ) {
+        // Initialize candidate container with dummy point
+        container.push((T::max_value(), self.root_node.stem()));
+
+        self.search_nearest_k(query, container, points_to_check);
+    }
+
+    /// Fills a `container` that has already been seeded with a dummy point with the nearest
+    /// neighbors of an already checked `query`, dispatching to the periodic query if the tree
+    /// has a boxsize. Only candidates closer than the dummy point are considered.
+    pub(crate) fn search_nearest_k<'i>(
+        &'i self,
+        query: &[NotNan<T>; D],
+        container: &mut Container<'i, T, D>,
+        points_to_check: &mut Vec<(&'i usize, &'i Point<T, D>, T)>,
+    ) {
+        // First get real image result
+        self.check_stem_k(query, &self.root_node, container, points_to_check);
+
+        // Then check all images closer than the current kth nearest neighbor
+        if let Some(ref boxsize) = self.boxsize {
+            let max_dist2 = *container.best_dist2();
+            for image in images_within(&self.metric, query, boxsize, &self.origin, max_dist2) {
+                self.check_stem_k(&image, &self.root_node, container, points_to_check);
+            }
         }
     }
 
@@ -59,7 +116,7 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
         // Recurse down (and then up and down) the stem
         self.check_stem_k(query, current_node, &mut container, &mut points_to_check);
 
-        container.index(self.start())
+        container.index(self.start(), &self.metric)
     }
 
     fn query_nearest_k_periodic<'q, 'i>(
@@ -97,25 +154,27 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
         for side in 0..D {
             // Do a single index here. This is equal to distance to lower side
             // safety: made safe by const generic
-            let query_component = unsafe { query.get_unchecked(side) };
+            let query_component =
+                &unsafe { query.get_unchecked(side) - self.origin.get_unchecked(side) };
 
             // Get distance to upper half
             // safety: made safe by const generic
             let upper = unsafe { boxsize.get_unchecked(side) } - query_component;
 
-            // !negative includes zero
+            // !negative includes zero. The query may be negative along nonperiodic
+            // dimensions, whose upper side is infinite.
             debug_assert!(!upper.is_sign_negative());
-            debug_assert!(!query_component.is_sign_negative());
+            debug_assert!(upper.is_infinite() || !query_component.is_sign_negative());
 
-            // Choose lesser of two and then square
-            closest_side_dist2[side] = upper.min(*query_component).powi(2);
+            // Choose lesser of two and then take its reduced distance
+            closest_side_dist2[side] = self.metric.axis_dist(side, *upper.min(*query_component));
         }
 
         // Find which images we need to check.
         // Initialize vector with real image (which we will remove later)
         let best_real_dist2 = real_image_container.best_dist2();
         let mut images_to_check = Vec::with_capacity(2_usize.pow(D as u32) - 1);
-        for image in 1..2_usize.pow(D as u32) {
+        for image in images(boxsize) {
             // Closest image in the form of bool array
             let closest_image = (0..D as u32).map(|idx| ((image / 2_usize.pow(idx)) % 2) == 1);
 
@@ -132,7 +191,7 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
                         None
                     }
                 })
-                .fold(T::zero(), |acc, x| acc + *x);
+                .fold(T::zero(), |acc, x| self.metric.accumulate(acc, *x));
 
             if dist_to_side_edge_or_other < *best_real_dist2 {
                 let mut image_to_check = query.clone();
@@ -150,7 +209,9 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
 
                         // safety: made safe by const generic
                         unsafe {
-                            if *query_component < boxsize_component / T::from(2.0).unwrap() {
+                            if query_component - self.origin.get_unchecked(idx)
+                                < boxsize_component / T::from(2.0).unwrap()
+                            {
                                 // Add if in lower half of box
                                 *image_to_check.get_unchecked_mut(idx) =
                                     query_component + boxsize_component
@@ -184,7 +245,7 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
             );
         }
 
-        real_image_container.index(self.start())
+        real_image_container.index(self.start(), &self.metric)
     }
 
     /// Upon checking that we are close to some other space during upward traversal of the tree,
@@ -231,7 +292,7 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
     {
         // Check all points in leaf
         for candidate in leaf_points {
-            new_best_kth(query, candidate, container);
+            new_best_kth(&self.metric, query, candidate, container);
         }
     }
 
@@ -268,7 +329,8 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
                         let (sibling_lower, sibling_upper) =
                             unsafe { self.nodes.get_unchecked(*left) }.get_bounds();
                         let dist_sq_to_space =
-                            calc_dist_sq_to_space(query, sibling_lower, sibling_upper);
+                            self.metric
+                                .dist_to_space(query, sibling_lower, sibling_upper);
                         if dist_sq_to_space <= *container.best_dist2() {
                             points_to_check.push((left, point, dist_sq_to_space));
                         }
@@ -281,7 +343,8 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
                         let (sibling_lower, sibling_upper) =
                             unsafe { self.nodes.get_unchecked(*right) }.get_bounds();
                         let dist_sq_to_space =
-                            calc_dist_sq_to_space(query, sibling_lower, sibling_upper);
+                            self.metric
+                                .dist_to_space(query, sibling_lower, sibling_upper);
                         if dist_sq_to_space <= *container.best_dist2() {
                             points_to_check.push((right, point, dist_sq_to_space));
                         }
@@ -319,6 +382,6 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
         'i: 'o,
         't: 'i,
     {
-        new_best_kth(query, stem, container);
+        new_best_kth(&self.metric, query, stem, container);
     }
 }
@@ -0,0 +1,290 @@
+use std::fmt::Debug;
+
+use crate::{
+    metric::Metric,
+    point::{Float, Point},
+    utils::{FnntwError, FnntwResult, QueryKResult},
+    Node, Tree,
+};
+use ordered_float::NotNan;
+
+use super::container::Container;
+
+impl<'t, T: Float + Debug, const D: usize, M: Metric<T, D>> Tree<'t, T, D, M> {
+    /// Finds the `k` nearest neighbors of every point in the tree, i.e. the self join of the
+    /// tree with `queries = get_data()`. The neighbors of `get_data()[i]` are found at
+    /// `i * k..(i + 1) * k`. If `exclude_self` is set, every point is excluded from its own
+    /// neighbors (see [`Tree::query_nearest_k_parallel_exclude_self`]), in which case at most
+    /// `get_data().len() - 1` neighbors are returned per point.
+    ///
+    /// Rather than querying every point from the root, the points of every leaf are queried
+    /// together: they are first seeded with each other as candidates, and then share a single
+    /// traversal of the tree that is pruned using the bounds of the leaf.
+    pub fn all_nearest_k(
+        &self,
+        k: usize,
+        exclude_self: bool,
+    ) -> FnntwResult<QueryKResult<'t, T, D>, T> {
+        // Check for valid k
+        let k = if exclude_self {
+            self.check_k_exclude(k)?
+        } else {
+            k.min(self.input.len())
+        };
+        if k == 0 {
+            return Err(FnntwError::InvalidK);
+        }
+
+        let len = self.input.len() * k;
+        let mut distances = Vec::with_capacity(len);
+        let mut indices = Vec::with_capacity(len);
+        #[cfg(not(feature = "no-position"))]
+        let mut positions = Vec::with_capacity(len);
+        let rows = Rows {
+            distances: distances.as_mut_ptr() as usize,
+            indices: indices.as_mut_ptr() as usize,
+            #[cfg(not(feature = "no-position"))]
+            positions: positions.as_mut_ptr() as usize,
+            k,
+        };
+
+        // Every point is either the point of a stem or in a leaf
+        let nodes: Vec<&Node<T, D>> = self
+            .nodes
+            .iter()
+            .chain(std::iter::once(&self.root_node))
+            .collect();
+        let buffers = || {
+            (
+                Vec::new(),
+                Vec::with_capacity(self.height_hint),
+                Vec::with_capacity(self.height_hint),
+            )
+        };
+        #[cfg(feature = "parallel")]
+        {
+            use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
+            nodes.par_iter().for_each_init(buffers, |buffers, node| {
+                self.all_nearest_k_node(node, k, exclude_self, buffers, &rows)
+            });
+        }
+        #[cfg(not(feature = "parallel"))]
+        {
+            let mut buffers = buffers();
+            for node in nodes {
+                self.all_nearest_k_node(node, k, exclude_self, &mut buffers, &rows);
+            }
+        }
+
+        // safety: every point has written its row
+        unsafe {
+            distances.set_len(len);
+            indices.set_len(len);
+            #[cfg(not(feature = "no-position"))]
+            positions.set_len(len);
+        }
+
+        Ok((
+            distances,
+            indices,
+            #[cfg(not(feature = "no-position"))]
+            positions,
+        ))
+    }
+
+    /// Finds the `k` nearest neighbors of the point of a stem, or of every point in a leaf,
+    /// writing them to their `rows`.
+    fn all_nearest_k_node<'i>(
+        &'i self,
+        node: &'i Node<T, D>,
+        k: usize,
+        exclude_self: bool,
+        (containers, nodes_to_check, points_to_check): &mut AllBuffers<'i, T, D>,
+        rows: &Rows,
+    ) {
+        match node {
+            Node::Stem { point, .. } => {
+                containers.resize_with(1, || Container::new(k));
+                let container = &mut containers[0];
+                container.set_exclude(exclude_self.then_some(*point));
+                self.fill_nearest_k(point.position(), container, points_to_check);
+                // safety: the container holds k candidates, and every point has its own row
+                unsafe { rows.write(container, point, self.start(), &self.metric) };
+            }
+            Node::Leaf { .. } => {
+                self.all_nearest_k_leaf(node, k, exclude_self, containers, nodes_to_check, rows)
+            }
+        }
+    }
+
+    /// Finds the `k` nearest neighbors of every point in `leaf`, writing them to their `rows`.
+    fn all_nearest_k_leaf<'i>(
+        &'i self,
+        leaf: &'i Node<T, D>,
+        k: usize,
+        exclude_self: bool,
+        containers: &mut Vec<Container<'i, T, D>>,
+        nodes_to_check: &mut Vec<&'i Node<T, D>>,
+        rows: &Rows,
+    ) {
+        let Node::Leaf {
+            points,
+            lower,
+            upper,
+        } = leaf
+        else {
+            unreachable!("this function should only be used on leaves")
+        };
+        let boxsize = self.boxsize.as_ref();
+
+        // Seed every point with a dummy candidate and the other points of the leaf
+        containers.resize_with(points.len(), || Container::new(k));
+        for (point, container) in points.iter().zip(containers.iter_mut()) {
+            container.set_exclude(exclude_self.then_some(*point));
+            container.push((T::max_value(), &points[0]));
+            for candidate in points {
+                new_best_kth_minimum_image(&self.metric, point, candidate, container, boxsize);
+            }
+        }
+
+        // Visit the rest of the tree, closest subtrees first
+        nodes_to_check.push(&self.root_node);
+        while let Some(node) = nodes_to_check.pop() {
+            if std::ptr::eq(node, leaf) {
+                continue;
+            }
+
+            // No point of the leaf can find a new neighbor in this subtree
+            let (node_lower, node_upper) = node.get_bounds();
+            let worst_dist2 = containers[..points.len()]
+                .iter()
+                .fold(T::zero(), |worst, container| {
+                    worst.max(*container.best_dist2())
+                });
+            let (min_dist2, _) = self
+                .metric
+                .dist_between_spaces(lower, upper, node_lower, node_upper, boxsize);
+            if min_dist2 > worst_dist2 {
+                continue;
+            }
+
+            match node {
+                Node::Stem {
+                    point, left, right, ..
+                } => {
+                    for (query, container) in points.iter().zip(containers.iter_mut()) {
+                        new_best_kth_minimum_image(&self.metric, query, point, container, boxsize);
+                    }
+
+                    // safety: indices are valid by construction, with the atomic lock on Vec<Node>
+                    let (left, right) = unsafe {
+                        (
+                            self.nodes.get_unchecked(*left),
+                            self.nodes.get_unchecked(*right),
+                        )
+                    };
+                    let dist2_to = |child: &Node<T, D>| {
+                        let (child_lower, child_upper) = child.get_bounds();
+                        self.metric
+                            .dist_between_spaces(lower, upper, child_lower, child_upper, boxsize)
+                            .0
+                    };
+                    if dist2_to(left) <= dist2_to(right) {
+                        nodes_to_check.extend([right, left]);
+                    } else {
+                        nodes_to_check.extend([left, right]);
+                    }
+                }
+                Node::Leaf {
+                    points: candidates, ..
+                } => {
+                    for (query, container) in points.iter().zip(containers.iter_mut()) {
+                        let (dist2, _) = self.metric.dist_between_spaces(
+                            query.position(),
+                            query.position(),
+                            node_lower,
+                            node_upper,
+                            boxsize,
+                        );
+                        if dist2 > *container.best_dist2() {
+                            continue;
+                        }
+                        for candidate in candidates {
+                            new_best_kth_minimum_image(
+                                &self.metric,
+                                query,
+                                candidate,
+                                container,
+                                boxsize,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        for (point, container) in points.iter().zip(containers.iter_mut()) {
+            // safety: the container holds k candidates, and every point has its own row
+            unsafe { rows.write(container, point, self.start(), &self.metric) };
+        }
+    }
+}
+
+/// Per-thread buffers: the containers of the points being queried, and the ledgers of
+/// nodes (for leaves) and points (for stems) left to check.
+type AllBuffers<'i, T, const D: usize> = (
+    Vec<Container<'i, T, D>>,
+    Vec<&'i Node<T, D>>,
+    Vec<(&'i usize, &'i Point<T, D>, T)>,
+);
+
+/// Pushes `candidate` if it is closer to `query` than the current kth nearest neighbor,
+/// using the minimum image distance if a `boxsize` is given.
+#[inline(always)]
+fn new_best_kth_minimum_image<'i, T: Float, const D: usize, M: Metric<T, D>>(
+    metric: &M,
+    query: &Point<T, D>,
+    candidate: &'i Point<T, D>,
+    container: &mut Container<'i, T, D>,
+    boxsize: Option<&[NotNan<T>; D]>,
+) {
+    let dist2 = match boxsize {
+        Some(boxsize) => metric.dist_periodic(query.position(), candidate.position(), boxsize),
+        None => metric.dist(query.position(), candidate.position()),
+    };
+    if dist2 <= *container.best_dist2() && !container.excludes(candidate) {
+        container.push((dist2, candidate));
+    }
+}
+
+/// Pointers (cast to `usize` to be shared across threads) to the `k` columns of the output rows.
+struct Rows {
+    distances: usize,
+    indices: usize,
+    #[cfg(not(feature = "no-position"))]
+    positions: usize,
+    k: usize,
+}
+
+impl Rows {
+    /// Writes the neighbors in `container` to the row of `point`.
+    ///
+    /// SAFETY: the container must hold `k` candidates, and no other thread may write this row.
+    unsafe fn write<T: Float, const D: usize, M: Metric<T, D>>(
+        &self,
+        container: &mut Container<'_, T, D>,
+        point: &Point<T, D>,
+        start: *const [NotNan<T>; D],
+        metric: &M,
+    ) {
+        let row = point.index(start) as usize * self.k;
+        container.index_sorted_into(
+            (self.distances as *mut T).add(row),
+            (self.indices as *mut u64).add(row),
+            #[cfg(not(feature = "no-position"))]
+            (self.positions as *mut [NotNan<T>; D]).add(row),
+            start,
+            metric,
+        );
+    }
+}
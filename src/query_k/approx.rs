@@ -0,0 +1,285 @@
+use std::fmt::Debug;
+
+use crate::{
+    metric::Metric,
+    periodic::images_within,
+    point::{Float, Point},
+    utils::{check_epsilon_return, FnntwError, FnntwResult, QueryKResult},
+    Node, Tree,
+};
+use ordered_float::NotNan;
+
+use super::container::Container;
+
+impl<'t, T: Float + Debug, const D: usize, M: Metric<T, D>> Tree<'t, T, D, M> {
+    /// Same as [`Tree::query_nearest_k`], except that the `i`th neighbor returned is only
+    /// guaranteed to be within a factor of `1 + epsilon` of the distance to the true `i`th
+    /// nearest neighbor. Subtrees are only visited if their distance times `1 + epsilon` is
+    /// closer than the current kth nearest neighbor, so larger `epsilon` visit fewer subtrees.
+    pub fn query_nearest_k_approx<'q>(
+        &'q self,
+        query: &'q [T; D],
+        k: usize,
+        epsilon: T,
+    ) -> FnntwResult<QueryKResult<'t, T, D>, T> {
+        // Check for valid query point and epsilon
+        let query: &[NotNan<T>; D] = &self.check_query(query)?;
+        let prune_scale = check_epsilon_return(epsilon, &self.metric)?;
+        let k = k.min(self.input.len());
+        if k == 0 {
+            return Err(FnntwError::InvalidK);
+        }
+
+        let mut container = Container::new(k);
+        let mut points_to_check = Vec::with_capacity(self.height_hint);
+        self.fill_nearest_k_approx(query, &mut container, &mut points_to_check, prune_scale);
+
+        let mut result: QueryKResult<'t, T, D> = (
+            Vec::with_capacity(k),
+            Vec::with_capacity(k),
+            #[cfg(not(feature = "no-position"))]
+            Vec::with_capacity(k),
+        );
+        // safety: there is capacity for k neighbors, and the container holds k candidates
+        unsafe {
+            container.index_sorted_into(
+                result.0.as_mut_ptr(),
+                result.1.as_mut_ptr(),
+                #[cfg(not(feature = "no-position"))]
+                result.2.as_mut_ptr(),
+                self.start(),
+                &self.metric,
+            );
+            result.0.set_len(k);
+            result.1.set_len(k);
+            #[cfg(not(feature = "no-position"))]
+            result.2.set_len(k);
+        }
+
+        Ok(result)
+    }
+
+    /// Performs [`Tree::query_nearest_k_approx`] for every query in `queries` in parallel. The
+    /// neighbors of `queries[i]` are found at `i * k..(i + 1) * k`.
+    #[cfg(feature = "parallel")]
+    pub fn query_nearest_k_parallel_approx<'q>(
+        &'q self,
+        queries: &'q [[T; D]],
+        k: usize,
+        epsilon: T,
+    ) -> FnntwResult<QueryKResult<'t, T, D>, T> {
+        use rayon::prelude::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+
+        // Check for valid epsilon once for all queries
+        let prune_scale = check_epsilon_return(epsilon, &self.metric)?;
+        let k = k.min(self.input.len());
+        if k == 0 {
+            return Err(FnntwError::InvalidK);
+        }
+
+        let mut distances = Vec::with_capacity(queries.len() * k);
+        let mut indices = Vec::with_capacity(queries.len() * k);
+        #[cfg(not(feature = "no-position"))]
+        let mut positions = Vec::with_capacity(queries.len() * k);
+        let dist_ptr_usize = distances.as_mut_ptr() as usize;
+        let idx_ptr_usize = indices.as_mut_ptr() as usize;
+        #[cfg(not(feature = "no-position"))]
+        let pos_ptr_usize = positions.as_mut_ptr() as usize;
+
+        // Query every point, reusing the per-thread buffers
+        queries.into_par_iter().enumerate().try_for_each_init(
+            || (Container::new(k), Vec::with_capacity(self.height_hint)),
+            |(container, points_to_check), (query_index, query)| -> FnntwResult<_, T> {
+                // Check for valid query point
+                let query: &[NotNan<T>; D] = &self.check_query(query)?;
+
+                self.fill_nearest_k_approx(query, container, points_to_check, prune_scale);
+
+                // safety: the rows are disjoint and all lie within the allocated capacity,
+                // and the container holds k candidates
+                unsafe {
+                    let row = query_index * k;
+                    container.index_sorted_into(
+                        (dist_ptr_usize as *mut T).add(row),
+                        (idx_ptr_usize as *mut u64).add(row),
+                        #[cfg(not(feature = "no-position"))]
+                        (pos_ptr_usize as *mut [NotNan<T>; D]).add(row),
+                        self.start(),
+                        &self.metric,
+                    );
+                }
+
+                Ok(())
+            },
+        )?;
+
+        // safety: every element up to queries.len() * k was just written
+        unsafe {
+            distances.set_len(queries.len() * k);
+            indices.set_len(queries.len() * k);
+            #[cfg(not(feature = "no-position"))]
+            positions.set_len(queries.len() * k);
+        }
+
+        Ok((
+            distances,
+            indices,
+            #[cfg(not(feature = "no-position"))]
+            positions,
+        ))
+    }
+
+    /// Same as [`Tree::fill_nearest_k`], except that subtrees and images are only visited if
+    /// their reduced distance times `prune_scale` is closer than the current kth nearest
+    /// neighbor. This is kept apart from the exact search so that it pays for no scaling.
+    pub(crate) fn fill_nearest_k_approx<'i>(
+        &'i self,
+        query: &[NotNan<T>; D],
+        container: &mut Container<'i, T, D>,
+        points_to_check: &mut Vec<(&'i usize, &'i Point<T, D>, T)>,
+        prune_scale: T,
+    ) {
+        // Initialize candidate container with dummy point
+        container.push((T::max_value(), self.root_node.stem()));
+
+        // First get real image result
+        self.check_stem_k_approx(
+            query,
+            &self.root_node,
+            container,
+            points_to_check,
+            prune_scale,
+        );
+
+        // Then check all images whose scaled distance is closer than the current kth nearest neighbor
+        if let Some(ref boxsize) = self.boxsize {
+            let max_dist2 = *container.best_dist2() / prune_scale;
+            for image in images_within(&self.metric, query, boxsize, &self.origin, max_dist2) {
+                self.check_stem_k_approx(
+                    &image,
+                    &self.root_node,
+                    container,
+                    points_to_check,
+                    prune_scale,
+                );
+            }
+        }
+    }
+
+    /// Upon checking that we are close to some other space during upward traversal of the tree,
+    /// this function is called to check candidates in the child space, appending any new candidate spaces
+    /// as we go along
+    fn check_child_k_approx<'i, 'o>(
+        &'i self,
+        query: &'o [NotNan<T>; D],
+        sibling: &usize,
+        // check's the parent of the sibling (also our parent)
+        stem: &'i Point<T, D>,
+        container: &'o mut Container<'i, T, D>,
+        points_to_check: &'o mut Vec<(&'i usize, &'i Point<T, D>, T)>,
+        prune_scale: T,
+    ) where
+        'i: 'o,
+        't: 'i,
+    {
+        // safety: indices are valid by construction, with the atomic lock on Vec<Node>
+        let sibling = unsafe { self.nodes.get_unchecked(*sibling) };
+        match sibling {
+            // Sibling is a leaf
+            Node::Leaf { points, .. } => {
+                // the stem here is the parent
+                self.check_parent_k(query, stem, container);
+                self.check_leaf_k(query, points.iter(), container)
+            }
+
+            // Sibling is a parent (e.g. for unbalanced tree)
+            Node::Stem { .. } => {
+                self.check_parent_k(query, stem, container);
+                self.check_stem_k_approx(query, sibling, container, points_to_check, prune_scale)
+            }
+        }
+    }
+
+    /// If sibling is a stem, then we need to recurse back down
+    fn check_stem_k_approx<'i, 'o>(
+        &'i self,
+        query: &'o [NotNan<T>; D],
+        stem: &'i Node<T, D>,
+        container: &'o mut Container<'i, T, D>,
+        points_to_check: &'o mut Vec<(&'i usize, &'i Point<T, D>, T)>,
+        prune_scale: T,
+    ) where
+        'i: 'o,
+        't: 'i,
+    {
+        // Navigate down the stems until we reach a leaf
+        let mut current_node = stem;
+
+        while current_node.is_stem() {
+            let next_leafnode = match current_node {
+                Node::Stem {
+                    ref split_dim,
+                    point,
+                    left,
+                    right,
+                    ..
+                } => {
+                    // Determine left/right split
+                    // safety: made safe by const generic
+                    if unsafe { query.get_unchecked(*split_dim) > point.get_unchecked(*split_dim) }
+                    {
+                        // Record sibling node and the dist_sq to sibling's associated space
+                        // safety: indices are valid by construction, with the atomic lock on Vec<Node>
+                        let (sibling_lower, sibling_upper) =
+                            unsafe { self.nodes.get_unchecked(*left) }.get_bounds();
+                        let dist_sq_to_space =
+                            self.metric
+                                .dist_to_space(query, sibling_lower, sibling_upper);
+                        if dist_sq_to_space * prune_scale <= *container.best_dist2() {
+                            points_to_check.push((left, point, dist_sq_to_space));
+                        }
+
+                        // Right Branch
+                        right
+                    } else {
+                        // Record sibling node and the dist_sq to its associated space
+                        // safety: indices are valid by construction, with the atomic lock on Vec<Node>
+                        let (sibling_lower, sibling_upper) =
+                            unsafe { self.nodes.get_unchecked(*right) }.get_bounds();
+                        let dist_sq_to_space =
+                            self.metric
+                                .dist_to_space(query, sibling_lower, sibling_upper);
+                        if dist_sq_to_space * prune_scale <= *container.best_dist2() {
+                            points_to_check.push((right, point, dist_sq_to_space));
+                        }
+
+                        // Left Branch
+                        left
+                    }
+                }
+                _ => unreachable!("we are traversing though stems"),
+            };
+
+            // Set leafnode
+            // safety: indices are valid by construction, with the atomic lock on Vec<Node>
+            current_node = unsafe { self.nodes.get_unchecked(*next_leafnode) };
+        }
+
+        // We are now at a leaf; check it
+        self.check_leaf_k(query, current_node.iter(), container);
+
+        // Now we empty out the queue
+        while let Some((sibling, parent, dist_sq_to_space)) = points_to_check.pop() {
+            if dist_sq_to_space * prune_scale < *container.best_dist2() {
+                self.check_child_k_approx(
+                    query,
+                    sibling,
+                    parent,
+                    container,
+                    points_to_check,
+                    prune_scale,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use std::fmt::Debug;
+
+use crate::{
+    metric::Metric,
+    point::{Float, Point},
+    utils::{check_radius_return, FnntwError, FnntwResult, QueryKResult},
+    Tree,
+};
+use ordered_float::NotNan;
+
+use super::container::Container;
+
+impl<'t, T: Float + Debug, const D: usize, M: Metric<T, D>> Tree<'t, T, D, M> {
+    /// Same as [`Tree::query_nearest_k`], except that no neighbor farther than `max_radius`
+    /// (inclusive) is returned, so fewer than `k` neighbors are returned if there are fewer than
+    /// `k` points within `max_radius` of the query. Subtrees farther than `max_radius` are pruned
+    /// from the start of the traversal.
+    ///
+    /// Note that `max_radius` is always a distance of the tree's metric (e.g. the not squared
+    /// euclidean distance), even when the `sqrt-dist2` feature is not enabled.
+    pub fn query_nearest_k_bounded<'q>(
+        &'q self,
+        query: &'q [T; D],
+        k: usize,
+        max_radius: T,
+    ) -> FnntwResult<QueryKResult<'t, T, D>, T> {
+        // Check for valid query point and radius
+        let query: &[NotNan<T>; D] = &self.check_query(query)?;
+        let max_dist2 = check_radius_return(max_radius, None, &self.metric)?;
+        let k = k.min(self.input.len());
+        if k == 0 {
+            return Err(FnntwError::InvalidK);
+        }
+
+        let mut container = Container::new(k);
+        let mut points_to_check = Vec::with_capacity(self.height_hint);
+        self.fill_nearest_k_bounded(query, max_dist2, &mut container, &mut points_to_check);
+
+        let mut result: QueryKResult<'t, T, D> = (
+            Vec::with_capacity(k),
+            Vec::with_capacity(k),
+            #[cfg(not(feature = "no-position"))]
+            Vec::with_capacity(k),
+        );
+        // safety: there is capacity for k neighbors, and the number of neighbors written is
+        // used as the length
+        unsafe {
+            let found = container.index_within_into(
+                max_dist2,
+                result.0.as_mut_ptr(),
+                result.1.as_mut_ptr(),
+                #[cfg(not(feature = "no-position"))]
+                result.2.as_mut_ptr(),
+                self.start(),
+                &self.metric,
+            );
+            result.0.set_len(found);
+            result.1.set_len(found);
+            #[cfg(not(feature = "no-position"))]
+            result.2.set_len(found);
+        }
+
+        Ok(result)
+    }
+
+    /// Performs [`Tree::query_nearest_k_bounded`] for every query in `queries` in parallel.
+    /// The neighbors of `queries[i]` are found at `i * k..(i + 1) * k`, where any missing
+    /// neighbors (beyond `max_radius`) are filled with an infinite distance and position and
+    /// an index of `u64::MAX`.
+    #[cfg(feature = "parallel")]
+    pub fn query_nearest_k_parallel_bounded<'q>(
+        &'q self,
+        queries: &'q [[T; D]],
+        k: usize,
+        max_radius: T,
+    ) -> FnntwResult<QueryKResult<'t, T, D>, T> {
+        use rayon::prelude::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+
+        // Check for valid radius once for all queries
+        let max_dist2 = check_radius_return(max_radius, None, &self.metric)?;
+        let k = k.min(self.input.len());
+        if k == 0 {
+            return Err(FnntwError::InvalidK);
+        }
+
+        let mut distances = Vec::with_capacity(queries.len() * k);
+        let mut indices = Vec::with_capacity(queries.len() * k);
+        #[cfg(not(feature = "no-position"))]
+        let mut positions = Vec::with_capacity(queries.len() * k);
+        let dist_ptr_usize = distances.as_mut_ptr() as usize;
+        let idx_ptr_usize = indices.as_mut_ptr() as usize;
+        #[cfg(not(feature = "no-position"))]
+        let pos_ptr_usize = positions.as_mut_ptr() as usize;
+
+        // Query every point, reusing the per-thread buffers
+        queries.into_par_iter().enumerate().try_for_each_init(
+            || (Container::new(k), Vec::with_capacity(self.height_hint)),
+            |(container, points_to_check), (query_index, query)| -> FnntwResult<_, T> {
+                // Check for valid query point
+                let query: &[NotNan<T>; D] = &self.check_query(query)?;
+
+                self.fill_nearest_k_bounded(query, max_dist2, container, points_to_check);
+
+                // safety: the rows are disjoint and all lie within the allocated capacity
+                unsafe {
+                    let row = query_index * k;
+                    let distances = (dist_ptr_usize as *mut T).add(row);
+                    let indices = (idx_ptr_usize as *mut u64).add(row);
+                    #[cfg(not(feature = "no-position"))]
+                    let positions = (pos_ptr_usize as *mut [NotNan<T>; D]).add(row);
+                    let found = container.index_within_into(
+                        max_dist2,
+                        distances,
+                        indices,
+                        #[cfg(not(feature = "no-position"))]
+                        positions,
+                        self.start(),
+                        &self.metric,
+                    );
+
+                    // Fill the rest of the row with sentinels
+                    for idx in found..k {
+                        *distances.add(idx) = T::infinity();
+                        *indices.add(idx) = u64::MAX;
+                        #[cfg(not(feature = "no-position"))]
+                        {
+                            *positions.add(idx) = [NotNan::new(T::infinity()).unwrap(); D];
+                        }
+                    }
+                }
+
+                Ok(())
+            },
+        )?;
+
+        // safety: every element up to queries.len() * k was just written
+        unsafe {
+            distances.set_len(queries.len() * k);
+            indices.set_len(queries.len() * k);
+            #[cfg(not(feature = "no-position"))]
+            positions.set_len(queries.len() * k);
+        }
+
+        Ok((
+            distances,
+            indices,
+            #[cfg(not(feature = "no-position"))]
+            positions,
+        ))
+    }
+
+    /// Fills an empty `container` with the nearest neighbors of an already checked `query`,
+    /// pruning everything farther than `max_dist2`. Some candidates beyond `max_dist2` (e.g. the
+    /// dummy point) may remain in the container and must be discarded.
+    fn fill_nearest_k_bounded<'i>(
+        &'i self,
+        query: &[NotNan<T>; D],
+        max_dist2: T,
+        container: &mut Container<'i, T, D>,
+        points_to_check: &mut Vec<(&'i usize, &'i Point<T, D>, T)>,
+    ) {
+        // Initialize candidate container with a dummy point just beyond the bound, so that
+        // every point on the bound displaces it
+        let dummy_dist2 = if max_dist2 > T::zero() {
+            max_dist2 + max_dist2 * T::epsilon()
+        } else {
+            T::min_positive_value()
+        };
+        container.push((dummy_dist2, self.root_node.stem()));
+
+        self.search_nearest_k(query, container, points_to_check);
+    }
+}
@@ -1,7 +1,7 @@
 use std::collections::BinaryHeap;
 
-use crate::utils::process_dist2;
 use crate::{
+    metric::Metric,
     point::{Float, Point},
     utils::QueryKResult,
     NotNan,
@@ -16,6 +16,7 @@ pub(crate) struct Candidate<'t, T: Float, const D: usize>((T, &'t Point<T, D>));
 pub struct Container<'t, T: Float, const D: usize> {
     items: BinaryHeap<Candidate<'t, T, D>>,
     k_or_datalen: usize,
+    exclude: Option<Point<T, D>>,
 }
 
 impl<'t, T: Float, const D: usize> Container<'t, T, D> {
@@ -23,9 +24,30 @@ impl<'t, T: Float, const D: usize> Container<'t, T, D> {
         Container {
             items: BinaryHeap::with_capacity(k),
             k_or_datalen: k,
+            exclude: None,
         }
     }
 
+    /// Sets the point (if any) that is never accepted as a candidate, e.g. the query itself.
+    pub(crate) fn set_exclude(&mut self, exclude: Option<Point<T, D>>) {
+        self.exclude = exclude;
+    }
+
+    // Euclidean needs access to this one
+    #[inline(always)]
+    pub(crate) fn excludes(&self, candidate: &Point<T, D>) -> bool {
+        self.exclude.as_ref() == Some(candidate)
+    }
+
+    /// Sets the number of neighbors to find, so that an empty container may be reused for
+    /// queries with different k.
+    #[cfg(feature = "parallel")]
+    pub(crate) fn set_k(&mut self, k: usize) {
+        debug_assert!(self.items.is_empty(), "container must be empty");
+        self.items.reserve(k);
+        self.k_or_datalen = k;
+    }
+
     #[allow(unused)]
     pub(super) fn check(&self, k_or_datalen: usize) -> bool {
         self.k_or_datalen == k_or_datalen
@@ -52,9 +74,120 @@ impl<'t, T: Float, const D: usize> Container<'t, T, D> {
         &self.items.peek().unwrap().0 .0
     }
 
+    /// Returns the farthest candidate, i.e. the only candidate when k = 1.
+    pub(crate) fn best(&self) -> (T, &'t Point<T, D>) {
+        self.items.peek().unwrap().0
+    }
+
+    /// Moves the squared distances of all candidates into `dist2`, sorted in ascending order,
+    /// emptying the container so that it may be reused.
+    pub(crate) fn drain_sorted_dist2(&mut self, dist2: &mut Vec<T>) {
+        dist2.clear();
+        dist2.extend(self.items.drain().map(|Candidate((dist2, _))| dist2));
+        dist2.sort_unstable_by(|a, b| a.partial_cmp(b).expect("distances are never nan"));
+    }
+
+    /// Writes the candidates at the (one-based, strictly increasing) `ranks` to the given
+    /// pointers, without sorting all of the candidates. This empties the container so that
+    /// it may be reused.
+    ///
+    /// SAFETY: the pointers must be valid for `ranks.len()` writes, and the container must hold
+    /// at least as many candidates as the largest rank.
+    pub(super) unsafe fn index_ranks_into<M: Metric<T, D>>(
+        &mut self,
+        ranks: &[usize],
+        distances: *mut T,
+        indices: *mut u64,
+        #[cfg(not(feature = "no-position"))] positions: *mut [NotNan<T>; D],
+        start: *const [NotNan<T>; D],
+        metric: &M,
+    ) {
+        let mut candidates = std::mem::take(&mut self.items).into_vec();
+
+        // Every selection partitions the candidates, so the next rank is found to the right
+        let mut lower = 0;
+        for (column, rank) in ranks.iter().enumerate() {
+            let (_, Candidate((dist2, neighbor)), _) =
+                candidates[lower..].select_nth_unstable(rank - 1 - lower);
+            *distances.add(column) = metric.process(*dist2);
+            *indices.add(column) = neighbor.index(start);
+            #[cfg(not(feature = "no-position"))]
+            {
+                *positions.add(column) = *neighbor.position();
+            }
+            lower = *rank;
+        }
+
+        // Keep the allocation for the next query
+        candidates.clear();
+        self.items = BinaryHeap::from(candidates);
+    }
+
+    /// Writes all candidates, sorted by distance, to the given pointers. This empties the
+    /// container so that it may be reused.
+    ///
+    /// SAFETY: the pointers must be valid for `k_or_datalen` writes, and the container must be full.
+    pub(super) unsafe fn index_sorted_into<M: Metric<T, D>>(
+        &mut self,
+        distances: *mut T,
+        indices: *mut u64,
+        #[cfg(not(feature = "no-position"))] positions: *mut [NotNan<T>; D],
+        start: *const [NotNan<T>; D],
+        metric: &M,
+    ) {
+        let mut candidates = std::mem::take(&mut self.items).into_sorted_vec();
+        for (idx, Candidate((dist2, neighbor))) in candidates.iter().enumerate() {
+            *distances.add(idx) = metric.process(*dist2);
+            *indices.add(idx) = neighbor.index(start);
+            #[cfg(not(feature = "no-position"))]
+            {
+                *positions.add(idx) = *neighbor.position();
+            }
+        }
+
+        // Keep the allocation for the next query
+        candidates.clear();
+        self.items = BinaryHeap::from(candidates);
+    }
+
+    /// Writes the candidates no farther than `max_dist2`, sorted by distance, to the given
+    /// pointers, returning how many were written. This empties the container so that it may
+    /// be reused.
+    ///
+    /// SAFETY: the pointers must be valid for `k_or_datalen` writes.
+    pub(super) unsafe fn index_within_into<M: Metric<T, D>>(
+        &mut self,
+        max_dist2: T,
+        distances: *mut T,
+        indices: *mut u64,
+        #[cfg(not(feature = "no-position"))] positions: *mut [NotNan<T>; D],
+        start: *const [NotNan<T>; D],
+        metric: &M,
+    ) -> usize {
+        let mut candidates = std::mem::take(&mut self.items).into_sorted_vec();
+        let within = candidates.partition_point(|Candidate((dist2, _))| *dist2 <= max_dist2);
+        for (idx, Candidate((dist2, neighbor))) in candidates[..within].iter().enumerate() {
+            *distances.add(idx) = metric.process(*dist2);
+            *indices.add(idx) = neighbor.index(start);
+            #[cfg(not(feature = "no-position"))]
+            {
+                *positions.add(idx) = *neighbor.position();
+            }
+        }
+
+        // Keep the allocation for the next query
+        candidates.clear();
+        self.items = BinaryHeap::from(candidates);
+        within
+    }
+
     #[allow(unused_mut)] // if sqrt-dist2 is on, mut is not used
 
-    pub(super) fn index<'i>(&mut self, start: *const [NotNan<T>; D]) -> QueryKResult<'t, T, D>
+    pub(crate) fn index<'i, M: Metric<T, D>>(
+        &mut self,
+        start: *const [NotNan<T>; D],
+        metric: &M,
+    ) -> QueryKResult<'t, T, D>
     where
         't: 'i,
     {
@@ -79,7 +212,7 @@ impl<'t, T: Float, const D: usize> Container<'t, T, D> {
         let mut idx = 0;
         for Candidate((mut dist2, neighbor)) in std::mem::take(&mut self.items).into_sorted_vec() {
             unsafe {
-                *ptrs.0.add(idx) = process_dist2(dist2);
+                *ptrs.0.add(idx) = metric.process(dist2);
                 *ptrs.1.add(idx) = neighbor.index(start);
                 #[cfg(not(feature = "no-position"))]
                 {
@@ -99,9 +232,10 @@ impl<'t, T: Float, const D: usize> Container<'t, T, D> {
 
     #[allow(unused_mut)] // if sqrt-dist2 is on, mut is not used
     #[allow(unused)]
-    pub(super) fn index_with<'i>(
+    pub(super) fn index_with<'i, M: Metric<T, D>>(
         mut self,
         start: *const [NotNan<T>; D],
+        metric: &M,
     ) -> (QueryKResult<'t, T, D>, Self)
     where
         't: 'i,
@@ -127,7 +261,7 @@ impl<'t, T: Float, const D: usize> Container<'t, T, D> {
         let mut idx = 0;
         for Candidate((mut dist2, neighbor)) in std::mem::take(&mut self.items).into_sorted_vec() {
             unsafe {
-                *ptrs.0.add(idx) = process_dist2(dist2);
+                *ptrs.0.add(idx) = metric.process(dist2);
                 *ptrs.1.add(idx) = neighbor.index(start);
                 #[cfg(not(feature = "no-position"))]
                 {
@@ -146,12 +280,13 @@ impl<'t, T: Float, const D: usize> Container<'t, T, D> {
     }
 
     #[cfg(all(feature = "parallel", feature = "no-position"))]
-    pub(super) fn index_into<'i>(
+    pub(super) fn index_into<'i, M: Metric<T, D>>(
         &mut self,
         distances_ptr: usize,
         indices_ptr: usize,
         query_index: usize,
         start: *const [NotNan<T>; D],
+        metric: &M,
     ) where
         't: 'i,
     {
@@ -163,7 +298,7 @@ impl<'t, T: Float, const D: usize> Container<'t, T, D> {
             let mut idx = 0;
             for Candidate((dist2, _)) in &neighbors {
                 unsafe {
-                    *dptr.add(query_index * self.k_or_datalen + idx) = process_dist2(*dist2);
+                    *dptr.add(query_index * self.k_or_datalen + idx) = metric.process(*dist2);
                 }
                 idx += 1;
             }
@@ -1,8 +1,8 @@
 #![cfg(all(feature = "parallel", feature = "no-position"))]
 use std::collections::BinaryHeap;
 
-use crate::utils::process_dist2;
 use crate::{
+    metric::Metric,
     point::{Float, Point},
     NotNan,
 };
@@ -163,13 +163,14 @@ impl<'t, T: Float, const D: usize> ContainerAxis<'t, T, D> {
     //     (result, self)
     // }
 
-    pub(super) fn index_into<'i>(
+    pub(super) fn index_into<'i, M: Metric<T, D>>(
         &mut self,
         ax_ptr: usize,
         nonax_ptr: usize,
         #[cfg(not(feature = "no-index"))] indices_ptr: usize,
         query_index: usize,
         #[cfg(not(feature = "no-index"))] start: *const [NotNan<T>; D],
+        metric: &M,
     ) where
         't: 'i,
     {
@@ -183,14 +184,14 @@ impl<'t, T: Float, const D: usize> ContainerAxis<'t, T, D> {
             let mut idx = 0;
             for CandidateAxis(((_d2, ax, _nonax), _p)) in &neighbors {
                 unsafe {
-                    *axptr.add(query_index * self.k_or_datalen + idx) = process_dist2(*ax);
+                    *axptr.add(query_index * self.k_or_datalen + idx) = metric.process(*ax);
                 }
                 idx += 1;
             }
             let mut idx = 0;
             for CandidateAxis(((_d2, _ax, nonax), _p)) in &neighbors {
                 unsafe {
-                    *nonaxptr.add(query_index * self.k_or_datalen + idx) = process_dist2(*nonax);
+                    *nonaxptr.add(query_index * self.k_or_datalen + idx) = metric.process(*nonax);
                 }
                 idx += 1;
             }
@@ -0,0 +1,151 @@
+use std::fmt::Debug;
+
+use crate::{
+    metric::Metric,
+    point::{Float, Point},
+    utils::{FnntwError, FnntwResult, QueryKResult},
+    Tree,
+};
+use ordered_float::NotNan;
+
+use super::container::Container;
+
+impl<'t, T: Float + Debug, const D: usize, M: Metric<T, D>> Tree<'t, T, D, M> {
+    /// Same as [`Tree::query_nearest_k`], except that the data point at index `exclude` (i.e.
+    /// `get_data()[exclude]`) is never returned as a neighbor. Since one point is excluded, at
+    /// most `get_data().len() - 1` neighbors are returned.
+    ///
+    /// Points that merely share the position of the excluded point are still returned.
+    pub fn query_nearest_k_exclude<'q>(
+        &'q self,
+        query: &'q [T; D],
+        k: usize,
+        exclude: usize,
+    ) -> FnntwResult<QueryKResult<'t, T, D>, T> {
+        // Check for valid query point, k, and excluded index
+        let query: &[NotNan<T>; D] = &self.check_query(query)?;
+        let k = self.check_k_exclude(k)?;
+        if exclude >= self.input.len() {
+            return Err(FnntwError::InvalidIndex);
+        }
+
+        let mut container = Container::new(k);
+        container.set_exclude(Some(self.point_at(exclude)));
+        let mut points_to_check = Vec::with_capacity(self.height_hint);
+        self.fill_nearest_k(query, &mut container, &mut points_to_check);
+
+        let mut result: QueryKResult<'t, T, D> = (
+            Vec::with_capacity(k),
+            Vec::with_capacity(k),
+            #[cfg(not(feature = "no-position"))]
+            Vec::with_capacity(k),
+        );
+        // safety: there is capacity for k neighbors, and the container holds k candidates
+        unsafe {
+            container.index_sorted_into(
+                result.0.as_mut_ptr(),
+                result.1.as_mut_ptr(),
+                #[cfg(not(feature = "no-position"))]
+                result.2.as_mut_ptr(),
+                self.start(),
+                &self.metric,
+            );
+            result.0.set_len(k);
+            result.1.set_len(k);
+            #[cfg(not(feature = "no-position"))]
+            result.2.set_len(k);
+        }
+
+        Ok(result)
+    }
+
+    /// Performs a k query for every query in `queries` in parallel, where the data point at the
+    /// same index as the query is never returned as a neighbor. This is intended for self joins,
+    /// i.e. with `queries = get_data()`, and returns `min(k, get_data().len() - 1)` neighbors per
+    /// query, with the neighbors of `queries[i]` found at `i * k..(i + 1) * k`.
+    ///
+    /// Points that merely share the position of the query are still returned.
+    #[cfg(feature = "parallel")]
+    pub fn query_nearest_k_parallel_exclude_self<'q>(
+        &'q self,
+        queries: &'q [[T; D]],
+        k: usize,
+    ) -> FnntwResult<QueryKResult<'t, T, D>, T> {
+        use rayon::prelude::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+
+        // Check for valid k once for all queries
+        let k = self.check_k_exclude(k)?;
+
+        let mut distances = Vec::with_capacity(queries.len() * k);
+        let mut indices = Vec::with_capacity(queries.len() * k);
+        #[cfg(not(feature = "no-position"))]
+        let mut positions = Vec::with_capacity(queries.len() * k);
+        let dist_ptr_usize = distances.as_mut_ptr() as usize;
+        let idx_ptr_usize = indices.as_mut_ptr() as usize;
+        #[cfg(not(feature = "no-position"))]
+        let pos_ptr_usize = positions.as_mut_ptr() as usize;
+
+        // Query every point, reusing the per-thread buffers
+        queries.into_par_iter().enumerate().try_for_each_init(
+            || (Container::new(k), Vec::with_capacity(self.height_hint)),
+            |(container, points_to_check), (query_index, query)| -> FnntwResult<_, T> {
+                // Check for valid query point
+                let query: &[NotNan<T>; D] = &self.check_query(query)?;
+
+                container.set_exclude(
+                    (query_index < self.input.len()).then(|| self.point_at(query_index)),
+                );
+                self.fill_nearest_k(query, container, points_to_check);
+
+                // safety: the rows are disjoint and all lie within the allocated capacity,
+                // and the container holds k candidates
+                unsafe {
+                    let row = query_index * k;
+                    container.index_sorted_into(
+                        (dist_ptr_usize as *mut T).add(row),
+                        (idx_ptr_usize as *mut u64).add(row),
+                        #[cfg(not(feature = "no-position"))]
+                        (pos_ptr_usize as *mut [NotNan<T>; D]).add(row),
+                        self.start(),
+                        &self.metric,
+                    );
+                }
+
+                Ok(())
+            },
+        )?;
+
+        // safety: every element up to queries.len() * k was just written
+        unsafe {
+            distances.set_len(queries.len() * k);
+            indices.set_len(queries.len() * k);
+            #[cfg(not(feature = "no-position"))]
+            positions.set_len(queries.len() * k);
+        }
+
+        Ok((
+            distances,
+            indices,
+            #[cfg(not(feature = "no-position"))]
+            positions,
+        ))
+    }
+
+    /// Checks that `k` neighbors can be found when excluding a point, returning the number
+    /// of neighbors that will be found.
+    pub(super) fn check_k_exclude(&self, k: usize) -> FnntwResult<usize, T> {
+        let k = k.min(self.input.len() - 1);
+        if k == 0 {
+            return Err(FnntwError::InvalidK);
+        }
+        Ok(k)
+    }
+
+    /// Returns the point at index `index` of the input data.
+    fn point_at(&self, index: usize) -> Point<T, D> {
+        // safety: callers check that the index is within the input data
+        Point {
+            ptr: unsafe { self.start().add(index) },
+        }
+    }
+}
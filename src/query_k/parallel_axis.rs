@@ -3,9 +3,11 @@
 use std::fmt::Debug;
 
 use crate::{
-    distance::{calc_dist_sq_to_space, new_best_kth_axis},
+    distance::new_best_kth_axis,
+    metric::Metric,
+    periodic::images,
     point::{Float, Point},
-    utils::{check_point_return, FnntwError, FnntwResult},
+    utils::{FnntwError, FnntwResult},
     Node, Tree,
 };
 use ordered_float::NotNan;
@@ -13,7 +15,7 @@ use ordered_float::NotNan;
 use super::container_axis::ContainerAxis;
 
 use crate::utils::QueryKAxisResult;
-impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
+impl<'t, T: Float + Debug, const D: usize, M: Metric<T, D>> Tree<'t, T, D, M> {
     pub fn query_nearest_k_parallel_axis<'q>(
         &'q self,
         queries: &'q [[T; D]],
@@ -39,7 +41,7 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
             queries.into_par_iter().enumerate().try_for_each(
                 |(query_index, query)| -> FnntwResult<_, T> {
                     // Check for valid query point
-                    let query: &[NotNan<T>; D] = check_point_return(query)?;
+                    let query: &[NotNan<T>; D] = &self.check_query(query)?;
 
                     let (mut container, mut point_vec) = (
                         ContainerAxis::new(k.min(self.data.len())),
@@ -68,7 +70,7 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
             queries.into_par_iter().enumerate().try_for_each(
                 |(query_index, query)| -> FnntwResult<_, T> {
                     // Check for valid query point
-                    let query: &[NotNan<T>; D] = check_point_return(query)?;
+                    let query: &[NotNan<T>; D] = &self.check_query(query)?;
 
                     let (mut container, mut point_vec) = (
                         ContainerAxis::new(k.min(self.data.len())),
@@ -141,6 +143,7 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
             query_index,
             #[cfg(not(feature = "no-index"))]
             self.start(),
+            &self.metric,
         );
         // index into clears!!
     }
@@ -180,25 +183,27 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
         for side in 0..D {
             // Do a single index here. This is equal to distance to lower side
             // safety: made safe by const generic
-            let query_component = unsafe { query.get_unchecked(side) };
+            let query_component =
+                &unsafe { query.get_unchecked(side) - self.origin.get_unchecked(side) };
 
             // Get distance to upper half
             // safety: made safe by const generic
             let upper = unsafe { boxsize.get_unchecked(side) } - query_component;
 
-            // !negative includes zero
+            // !negative includes zero. The query may be negative along nonperiodic
+            // dimensions, whose upper side is infinite.
             debug_assert!(!upper.is_sign_negative());
-            debug_assert!(!query_component.is_sign_negative());
+            debug_assert!(upper.is_infinite() || !query_component.is_sign_negative());
 
-            // Choose lesser of two and then square
-            closest_side_dist2[side] = upper.min(*query_component).powi(2);
+            // Choose lesser of two and then take its reduced distance
+            closest_side_dist2[side] = self.metric.axis_dist(side, *upper.min(*query_component));
         }
 
         // Find which images we need to check.
         // Initialize vector with real image (which we will remove later)
         let best_real_dist2 = real_image_container.best_dist2();
         let mut images_to_check = Vec::with_capacity(2_usize.pow(D as u32) - 1);
-        for image in 1..2_usize.pow(D as u32) {
+        for image in images(boxsize) {
             // Closest image in the form of bool array
             let closest_image = (0..D as u32).map(|idx| ((image / 2_usize.pow(idx)) % 2) == 1);
 
@@ -215,7 +220,7 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
                         None
                     }
                 })
-                .fold(T::zero(), |acc, x| acc + *x);
+                .fold(T::zero(), |acc, x| self.metric.accumulate(acc, *x));
 
             if dist_to_side_edge_or_other < *best_real_dist2 {
                 let mut image_to_check = query.clone();
@@ -233,7 +238,9 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
 
                         // safety: made safe by const generic
                         unsafe {
-                            if *query_component < boxsize_component / T::from(2.0).unwrap() {
+                            if query_component - self.origin.get_unchecked(idx)
+                                < boxsize_component / T::from(2.0).unwrap()
+                            {
                                 // Add if in lower half of box
                                 *image_to_check.get_unchecked_mut(idx) =
                                     query_component + boxsize_component
@@ -277,6 +284,7 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
             query_index,
             #[cfg(not(feature = "no-index"))]
             self.start(),
+            &self.metric,
         );
     }
 
@@ -326,7 +334,7 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
     {
         // Check all points in leaf
         for candidate in leaf_points {
-            new_best_kth_axis(query, candidate, container, axis);
+            new_best_kth_axis(&self.metric, query, candidate, container, axis);
         }
     }
 
@@ -364,7 +372,8 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
                         let (sibling_lower, sibling_upper) =
                             unsafe { self.nodes.get_unchecked(*left) }.get_bounds();
                         let dist_sq_to_space =
-                            calc_dist_sq_to_space(query, sibling_lower, sibling_upper);
+                            self.metric
+                                .dist_to_space(query, sibling_lower, sibling_upper);
                         points_to_check.push((left, point, dist_sq_to_space));
 
                         // Right Branch
@@ -375,7 +384,8 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
                         let (sibling_lower, sibling_upper) =
                             unsafe { self.nodes.get_unchecked(*right) }.get_bounds();
                         let dist_sq_to_space =
-                            calc_dist_sq_to_space(query, sibling_lower, sibling_upper);
+                            self.metric
+                                .dist_to_space(query, sibling_lower, sibling_upper);
                         points_to_check.push((right, point, dist_sq_to_space));
 
                         // Left Branch
@@ -412,6 +422,6 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
         'i: 'o,
         't: 'i,
     {
-        new_best_kth_axis(query, stem, container, axis);
+        new_best_kth_axis(&self.metric, query, stem, container, axis);
     }
 }
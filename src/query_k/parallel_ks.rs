@@ -0,0 +1,103 @@
+#![cfg(feature = "parallel")]
+
+use std::fmt::Debug;
+
+use crate::{
+    metric::Metric,
+    point::Float,
+    utils::{FnntwError, FnntwResult, QueryKCsrResult},
+    Tree,
+};
+use ordered_float::NotNan;
+
+use super::container::Container;
+
+impl<'t, T: Float + Debug, const D: usize, M: Metric<T, D>> Tree<'t, T, D, M> {
+    /// Performs a k query with a different `ks[i]` for every query `queries[i]` in parallel.
+    /// The result is returned in compressed sparse row (CSR) format, i.e. as
+    /// (`offsets`, `distances`, `indices`), where the `min(ks[i], get_data().len())` nearest
+    /// neighbors of `queries[i]` are found at `offsets[i]..offsets[i + 1]`, sorted by distance.
+    pub fn query_nearest_k_parallel_ks<'q>(
+        &'q self,
+        queries: &'q [[T; D]],
+        ks: &[usize],
+    ) -> FnntwResult<QueryKCsrResult<'t, T, D>, T> {
+        use rayon::prelude::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+
+        if ks.len() != queries.len() {
+            return Err(FnntwError::KLengthMismatch);
+        }
+
+        // Compute the offsets of every query's neighbors
+        let mut offsets = Vec::with_capacity(queries.len() + 1);
+        offsets.push(0);
+        let mut total = 0;
+        for k in ks {
+            total += k.min(&self.input.len());
+            offsets.push(total);
+        }
+
+        let mut distances = Vec::with_capacity(total);
+        let mut indices = Vec::with_capacity(total);
+        #[cfg(not(feature = "no-position"))]
+        let mut positions = Vec::with_capacity(total);
+        let dist_ptr_usize = distances.as_mut_ptr() as usize;
+        let idx_ptr_usize = indices.as_mut_ptr() as usize;
+        #[cfg(not(feature = "no-position"))]
+        let pos_ptr_usize = positions.as_mut_ptr() as usize;
+
+        // Query every point, reusing the per-thread buffers across different k
+        queries.into_par_iter().enumerate().try_for_each_init(
+            || (Container::new(0), Vec::with_capacity(self.height_hint)),
+            |(container, points_to_check), (query_index, query)| -> FnntwResult<_, T> {
+                // Check for valid query point
+                let query: &[NotNan<T>; D] = &self.check_query(query)?;
+
+                // safety: offsets has queries.len() + 1 elements
+                let (start, end) = unsafe {
+                    (
+                        *offsets.get_unchecked(query_index),
+                        *offsets.get_unchecked(query_index + 1),
+                    )
+                };
+                if start == end {
+                    return Ok(());
+                }
+
+                container.set_k(end - start);
+                self.fill_nearest_k(query, container, points_to_check);
+
+                // safety: the rows are disjoint and all lie within the allocated capacity,
+                // and the container holds end - start candidates
+                unsafe {
+                    container.index_sorted_into(
+                        (dist_ptr_usize as *mut T).add(start),
+                        (idx_ptr_usize as *mut u64).add(start),
+                        #[cfg(not(feature = "no-position"))]
+                        (pos_ptr_usize as *mut [NotNan<T>; D]).add(start),
+                        self.start(),
+                        &self.metric,
+                    );
+                }
+
+                Ok(())
+            },
+        )?;
+
+        // safety: every element up to total was just written
+        unsafe {
+            distances.set_len(total);
+            indices.set_len(total);
+            #[cfg(not(feature = "no-position"))]
+            positions.set_len(total);
+        }
+
+        Ok((
+            offsets,
+            distances,
+            indices,
+            #[cfg(not(feature = "no-position"))]
+            positions,
+        ))
+    }
+}
@@ -3,15 +3,17 @@
 use std::fmt::Debug;
 
 use crate::{
+    metric::Metric,
+    periodic::images,
     point::{Float, Point},
-    utils::{check_point_return, FnntwResult, QueryKResult},
+    utils::{FnntwResult, QueryKResult},
     Node, Tree,
 };
 use ordered_float::NotNan;
 
 use super::container::Container;
 
-impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
+impl<'t, T: Float + Debug, const D: usize, M: Metric<T, D>> Tree<'t, T, D, M> {
     pub fn query_nearest_k_parallel_with<'q>(
         &'q self,
         queries: &'q [[T; D]],
@@ -42,7 +44,7 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
             queries.into_par_iter().enumerate().try_for_each(
                 |(query_index, query)| -> FnntwResult<_, T> {
                     // Check for valid query point
-                    let query: &[NotNan<T>; D] = check_point_return(query)?;
+                    let query: &[NotNan<T>; D] = &self.check_query(query)?;
 
                     // Get pre-allocated buffers if available
                     let (ref mut container, ref mut point_vec) =
@@ -67,7 +69,7 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
             queries.into_par_iter().enumerate().try_for_each(
                 |(query_index, query)| -> FnntwResult<_, T> {
                     // Check for valid query point
-                    let query: &[NotNan<T>; D] = check_point_return(query)?;
+                    let query: &[NotNan<T>; D] = &self.check_query(query)?;
 
                     // let (mut container, mut point_vec) =
                     //     (Container::new(k), Vec::with_capacity(self.height_hint));
@@ -110,7 +112,7 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
 
     fn query_nearest_k_nonperiodic_into_with<'q>(
         &'q self,
-        query: &'q [NotNan<T>; D],
+        query: &[NotNan<T>; D],
         _k: usize,
         container: &mut Container<'q, T, D>,
         points_to_check: &mut Vec<(&'q usize, &'q Point<T, D>, T)>,
@@ -128,12 +130,18 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
         self.check_stem_k(query, current_node, container, points_to_check);
 
         // Write to given vector
-        container.index_into(distances_ptr, indices_ptr, query_index, self.start());
+        container.index_into(
+            distances_ptr,
+            indices_ptr,
+            query_index,
+            self.start(),
+            &self.metric,
+        );
     }
 
     fn query_nearest_k_periodic_into_with<'q, 'i>(
         &'q self,
-        query: &'q [NotNan<T>; D],
+        query: &[NotNan<T>; D],
         _k: usize,
         boxsize: &[NotNan<T>; D],
         container: &mut Container<'q, T, D>,
@@ -161,25 +169,27 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
         for side in 0..D {
             // Do a single index here. This is equal to distance to lower side
             // safety: made safe by const generic
-            let query_component = unsafe { query.get_unchecked(side) };
+            let query_component =
+                &unsafe { query.get_unchecked(side) - self.origin.get_unchecked(side) };
 
             // Get distance to upper half
             // safety: made safe by const generic
             let upper = unsafe { boxsize.get_unchecked(side) } - query_component;
 
-            // !negative includes zero
+            // !negative includes zero. The query may be negative along nonperiodic
+            // dimensions, whose upper side is infinite.
             debug_assert!(!upper.is_sign_negative());
-            debug_assert!(!query_component.is_sign_negative());
+            debug_assert!(upper.is_infinite() || !query_component.is_sign_negative());
 
-            // Choose lesser of two and then square
-            closest_side_dist2[side] = upper.min(*query_component).powi(2);
+            // Choose lesser of two and then take its reduced distance
+            closest_side_dist2[side] = self.metric.axis_dist(side, *upper.min(*query_component));
         }
 
         // Find which images we need to check.
         // Initialize vector with real image (which we will remove later)
         let best_real_dist2 = real_image_container.best_dist2();
         let mut images_to_check = Vec::with_capacity(2_usize.pow(D as u32) - 1);
-        for image in 1..2_usize.pow(D as u32) {
+        for image in images(boxsize) {
             // Closest image in the form of bool array
             let closest_image = (0..D as u32).map(|idx| ((image / 2_usize.pow(idx)) % 2) == 1);
 
@@ -196,7 +206,7 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
                         None
                     }
                 })
-                .fold(T::zero(), |acc, x| acc + *x);
+                .fold(T::zero(), |acc, x| self.metric.accumulate(acc, *x));
 
             if dist_to_side_edge_or_other < *best_real_dist2 {
                 let mut image_to_check = query.clone();
@@ -214,7 +224,9 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
 
                         // safety: made safe by const generic
                         unsafe {
-                            if *query_component < boxsize_component / T::from(2.0).unwrap() {
+                            if query_component - self.origin.get_unchecked(idx)
+                                < boxsize_component / T::from(2.0).unwrap()
+                            {
                                 // Add if in lower half of box
                                 *image_to_check.get_unchecked_mut(idx) =
                                     query_component + boxsize_component
@@ -249,6 +261,12 @@ impl<'t, T: Float + Debug, const D: usize> Tree<'t, T, D> {
             );
         }
 
-        real_image_container.index_into(distances_ptr, indices_ptr, query_index, self.start());
+        real_image_container.index_into(
+            distances_ptr,
+            indices_ptr,
+            query_index,
+            self.start(),
+            &self.metric,
+        );
     }
 }
@@ -0,0 +1,143 @@
+use std::fmt::Debug;
+
+use crate::{
+    metric::Metric,
+    point::Float,
+    utils::{FnntwError, FnntwResult, QueryKResult},
+    Tree,
+};
+use ordered_float::NotNan;
+
+use super::container::Container;
+
+impl<'t, T: Float + Debug, const D: usize, M: Metric<T, D>> Tree<'t, T, D, M> {
+    /// Given a query point `query`, query the tree and return only the neighbors at the given
+    /// `ranks`, where rank 1 is the nearest neighbor. The `ranks` must be strictly increasing.
+    ///
+    /// This is equivalent to selecting the `ranks[i] - 1` columns of [`Tree::query_nearest_k`]
+    /// with `k = ranks.last()`, but the remaining neighbors are neither sorted nor returned.
+    pub fn query_nearest_k_ranks<'q>(
+        &'q self,
+        query: &'q [T; D],
+        ranks: &[usize],
+    ) -> FnntwResult<QueryKResult<'t, T, D>, T> {
+        // Check for valid query point and ranks
+        let query: &[NotNan<T>; D] = &self.check_query(query)?;
+        let max_rank = self.check_ranks(ranks)?;
+
+        let mut container = Container::new(max_rank);
+        let mut points_to_check = Vec::with_capacity(self.height_hint);
+        self.fill_nearest_k(query, &mut container, &mut points_to_check);
+
+        let mut result: QueryKResult<'t, T, D> = (
+            Vec::with_capacity(ranks.len()),
+            Vec::with_capacity(ranks.len()),
+            #[cfg(not(feature = "no-position"))]
+            Vec::with_capacity(ranks.len()),
+        );
+        // safety: there is capacity for every rank, and the container holds max_rank candidates
+        unsafe {
+            container.index_ranks_into(
+                ranks,
+                result.0.as_mut_ptr(),
+                result.1.as_mut_ptr(),
+                #[cfg(not(feature = "no-position"))]
+                result.2.as_mut_ptr(),
+                self.start(),
+                &self.metric,
+            );
+            result.0.set_len(ranks.len());
+            result.1.set_len(ranks.len());
+            #[cfg(not(feature = "no-position"))]
+            result.2.set_len(ranks.len());
+        }
+
+        Ok(result)
+    }
+
+    /// Performs [`Tree::query_nearest_k_ranks`] for every query in `queries` in parallel. The
+    /// neighbors of `queries[i]` are found at `i * ranks.len()..(i + 1) * ranks.len()`.
+    #[cfg(feature = "parallel")]
+    pub fn query_nearest_k_ranks_parallel<'q>(
+        &'q self,
+        queries: &'q [[T; D]],
+        ranks: &[usize],
+    ) -> FnntwResult<QueryKResult<'t, T, D>, T> {
+        use rayon::prelude::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+
+        // Check for valid ranks once for all queries
+        let max_rank = self.check_ranks(ranks)?;
+        let num_ranks = ranks.len();
+
+        let mut distances = Vec::with_capacity(queries.len() * num_ranks);
+        let mut indices = Vec::with_capacity(queries.len() * num_ranks);
+        #[cfg(not(feature = "no-position"))]
+        let mut positions = Vec::with_capacity(queries.len() * num_ranks);
+        let dist_ptr_usize = distances.as_mut_ptr() as usize;
+        let idx_ptr_usize = indices.as_mut_ptr() as usize;
+        #[cfg(not(feature = "no-position"))]
+        let pos_ptr_usize = positions.as_mut_ptr() as usize;
+
+        // Query every point, reusing the per-thread buffers
+        queries.into_par_iter().enumerate().try_for_each_init(
+            || {
+                (
+                    Container::new(max_rank),
+                    Vec::with_capacity(self.height_hint),
+                )
+            },
+            |(container, points_to_check), (query_index, query)| -> FnntwResult<_, T> {
+                // Check for valid query point
+                let query: &[NotNan<T>; D] = &self.check_query(query)?;
+
+                self.fill_nearest_k(query, container, points_to_check);
+
+                // safety: the rows are disjoint and all lie within the allocated capacity,
+                // and the container holds max_rank candidates
+                unsafe {
+                    let row = query_index * num_ranks;
+                    container.index_ranks_into(
+                        ranks,
+                        (dist_ptr_usize as *mut T).add(row),
+                        (idx_ptr_usize as *mut u64).add(row),
+                        #[cfg(not(feature = "no-position"))]
+                        (pos_ptr_usize as *mut [NotNan<T>; D]).add(row),
+                        self.start(),
+                        &self.metric,
+                    );
+                }
+
+                Ok(())
+            },
+        )?;
+
+        // safety: every element up to queries.len() * num_ranks was just written
+        unsafe {
+            distances.set_len(queries.len() * num_ranks);
+            indices.set_len(queries.len() * num_ranks);
+            #[cfg(not(feature = "no-position"))]
+            positions.set_len(queries.len() * num_ranks);
+        }
+
+        Ok((
+            distances,
+            indices,
+            #[cfg(not(feature = "no-position"))]
+            positions,
+        ))
+    }
+
+    /// Checks that `ranks` are valid, returning the largest rank.
+    fn check_ranks(&self, ranks: &[usize]) -> FnntwResult<usize, T> {
+        match (ranks.first(), ranks.last()) {
+            (Some(&first), Some(&last))
+                if first >= 1
+                    && last <= self.input.len()
+                    && ranks.windows(2).all(|ranks| ranks[0] < ranks[1]) =>
+            {
+                Ok(last)
+            }
+            _ => Err(FnntwError::InvalidRanks),
+        }
+    }
+}
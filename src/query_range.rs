@@ -0,0 +1,193 @@
+use std::fmt::Debug;
+
+use crate::{
+    metric::Metric,
+    point::{Float, Point},
+    utils::{check_point_return, FnntwError, FnntwResult},
+    Node, Tree,
+};
+use ordered_float::NotNan;
+
+impl<'t, T: Float + Debug, const D: usize, M: Metric<T, D>> Tree<'t, T, D, M> {
+    /// Returns the indices of all points within the axis-aligned box defined by the corners
+    /// `lower` and `upper` (inclusive), in ascending order.
+    ///
+    /// If the tree has a boxsize, the range wraps around the periodic box, so the corners may
+    /// lie outside of the box (e.g. `lower = [-0.1; D]` and `upper = [0.1; D]` selects the points
+    /// near every corner of the box). A range at least as wide as the box along some dimension
+    /// selects the whole box along that dimension.
+    pub fn query_range(&self, lower: &[T; D], upper: &[T; D]) -> FnntwResult<Vec<u64>, T> {
+        // Check for valid range
+        let (Ok(lower), Ok(upper)) = (check_point_return(lower), check_point_return(upper)) else {
+            return Err(FnntwError::InvalidRange);
+        };
+        if lower.iter().zip(upper).any(|(lower, upper)| lower > upper) {
+            return Err(FnntwError::InvalidRange);
+        }
+
+        let mut neighbors = Vec::new();
+        let mut nodes_to_check = Vec::with_capacity(self.height_hint);
+        if let Some(ref boxsize) = self.boxsize {
+            // Periodic query, over the disjoint ranges within the box
+            for (lower, upper) in wrapped_ranges(lower, upper, boxsize, &self.origin) {
+                self.check_range(&lower, &upper, &mut neighbors, &mut nodes_to_check);
+            }
+        } else {
+            // Nonperiodic query
+            self.check_range(lower, upper, &mut neighbors, &mut nodes_to_check);
+        }
+
+        let mut indices: Vec<u64> = neighbors
+            .into_iter()
+            .map(|neighbor| neighbor.index(self.start()))
+            .collect();
+        indices.sort_unstable();
+        Ok(indices)
+    }
+
+    /// Traverses the tree, pushing every point within the range onto `neighbors`. Any stem or
+    /// leaf whose bounding box is inside the range is accepted in full, and any whose bounding
+    /// box is disjoint from the range is pruned.
+    fn check_range<'i>(
+        &'i self,
+        lower: &[NotNan<T>; D],
+        upper: &[NotNan<T>; D],
+        neighbors: &mut Vec<&'i Point<T, D>>,
+        nodes_to_check: &mut Vec<&'i Node<T, D>>,
+    ) {
+        nodes_to_check.push(&self.root_node);
+
+        while let Some(node) = nodes_to_check.pop() {
+            let (node_lower, node_upper) = node.get_bounds();
+            if !overlaps(node_lower, node_upper, lower, upper) {
+                continue;
+            }
+            if contains(lower, upper, node_lower, node_upper) {
+                self.push_subtree(node, neighbors);
+                continue;
+            }
+
+            match node {
+                Node::Stem {
+                    point, left, right, ..
+                } => {
+                    // The stem point is not in either child
+                    if contains(lower, upper, point.position(), point.position()) {
+                        neighbors.push(point);
+                    }
+
+                    // safety: indices are valid by construction, with the atomic lock on Vec<Node>
+                    unsafe {
+                        nodes_to_check.push(self.nodes.get_unchecked(*left));
+                        nodes_to_check.push(self.nodes.get_unchecked(*right));
+                    }
+                }
+                Node::Leaf { points, .. } => {
+                    neighbors.extend(points.iter().filter(|candidate| {
+                        contains(lower, upper, candidate.position(), candidate.position())
+                    }));
+                }
+            }
+        }
+    }
+
+    /// Pushes every point in the subtree of `node` onto `neighbors`.
+    fn push_subtree<'i>(&'i self, node: &'i Node<T, D>, neighbors: &mut Vec<&'i Point<T, D>>) {
+        match node {
+            Node::Stem {
+                point, left, right, ..
+            } => {
+                neighbors.push(point);
+                // safety: indices are valid by construction, with the atomic lock on Vec<Node>
+                unsafe {
+                    self.push_subtree(self.nodes.get_unchecked(*left), neighbors);
+                    self.push_subtree(self.nodes.get_unchecked(*right), neighbors);
+                }
+            }
+            Node::Leaf { points, .. } => neighbors.extend(points),
+        }
+    }
+}
+
+/// Whether the space defined by `lower_a` and `upper_a` overlaps the one defined by `lower_b`
+/// and `upper_b`.
+fn overlaps<T: Float, const D: usize>(
+    lower_a: &[NotNan<T>; D],
+    upper_a: &[NotNan<T>; D],
+    lower_b: &[NotNan<T>; D],
+    upper_b: &[NotNan<T>; D],
+) -> bool {
+    (0..D).all(|i| lower_a[i] <= upper_b[i] && lower_b[i] <= upper_a[i])
+}
+
+/// Whether the space defined by `lower` and `upper` contains the one defined by `inner_lower`
+/// and `inner_upper`.
+fn contains<T: Float, const D: usize>(
+    lower: &[NotNan<T>; D],
+    upper: &[NotNan<T>; D],
+    inner_lower: &[NotNan<T>; D],
+    inner_upper: &[NotNan<T>; D],
+) -> bool {
+    (0..D).all(|i| lower[i] <= inner_lower[i] && inner_upper[i] <= upper[i])
+}
+
+/// The `lower` and `upper` corners of a range.
+type Corners<T, const D: usize> = ([NotNan<T>; D], [NotNan<T>; D]);
+
+/// Splits a range that may extend beyond the periodic box into disjoint ranges within the box.
+///
+/// Along every dimension, the range is shifted into the box (whose lower corner is `origin`)
+/// and split at the edge of the box if needed. Since data may lie on either face of the box, which are the same point in the
+/// periodic box, a range touching one face also includes the other.
+fn wrapped_ranges<T: Float, const D: usize>(
+    lower: &[NotNan<T>; D],
+    upper: &[NotNan<T>; D],
+    boxsize: &[NotNan<T>; D],
+    origin: &[NotNan<T>; D],
+) -> Vec<Corners<T, D>> {
+    let zero = NotNan::new(T::zero()).unwrap();
+
+    let mut ranges = vec![(*lower, *upper)];
+    for dim in 0..D {
+        let side = boxsize[dim];
+        if side.is_infinite() {
+            // Nonperiodic dimension
+            continue;
+        }
+        let width = upper[dim] - lower[dim];
+        let origin = origin[dim];
+
+        // Intervals within the box along this dimension, relative to its lower corner
+        let intervals = if width >= side {
+            vec![(zero, side)]
+        } else {
+            let relative = lower[dim] - origin;
+            let shift = NotNan::new((*relative / *side).floor() * *side).unwrap();
+            let lower = (relative - shift).min(side);
+            let upper = lower + width;
+            if upper >= side {
+                vec![(lower, side), (zero, upper - side)]
+            } else if lower == zero {
+                vec![(zero, upper), (side, side)]
+            } else {
+                vec![(lower, upper)]
+            }
+        };
+
+        ranges = ranges
+            .into_iter()
+            .flat_map(|(lower, upper)| {
+                intervals
+                    .iter()
+                    .map(move |(interval_lower, interval_upper)| {
+                        let (mut lower, mut upper) = (lower, upper);
+                        lower[dim] = interval_lower + origin;
+                        upper[dim] = interval_upper + origin;
+                        (lower, upper)
+                    })
+            })
+            .collect();
+    }
+
+    ranges
+}
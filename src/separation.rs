@@ -0,0 +1,86 @@
+use std::fmt::Debug;
+
+use crate::{
+    metric::Metric,
+    point::Float,
+    utils::{FnntwError, FnntwResult, QuerySeparationResult},
+    Tree,
+};
+use ordered_float::NotNan;
+
+impl<'t, T: Float + Debug, const D: usize, M: Metric<T, D>> Tree<'t, T, D, M> {
+    /// Same as [`Tree::query_nearest_k`], except that the separation vectors from the query to
+    /// its neighbors (i.e. `neighbor - query`) are returned instead of their positions. If the
+    /// tree has a boxsize, these are the minimum image separations, i.e. they are computed with
+    /// the same image that produced the minimum image distance.
+    ///
+    /// Note that the minimum image is found independently along every periodic axis. This is
+    /// only the image with the minimum distance for axis-separable metrics, i.e. metrics that
+    /// accumulate the distances along every axis as in [`Metric::dist_periodic`] (such as all
+    /// metrics provided by this crate), and not for custom metrics that override it otherwise.
+    pub fn query_nearest_k_separation(
+        &self,
+        query: &[T; D],
+        k: usize,
+    ) -> FnntwResult<QuerySeparationResult<T, D>, T> {
+        // Check for valid query point, wrapping it into the box
+        let query: &[NotNan<T>; D] = &self.check_query(query)?;
+        if k == 0 {
+            return Err(FnntwError::InvalidK);
+        }
+
+        let result = self.query_nearest_k_checked(query, k);
+        let separations = self.separations(query, &result.1);
+
+        Ok((result.0, result.1, separations))
+    }
+
+    /// Same as [`Tree::query_ball_point`], except that the separation vectors from the query
+    /// to its neighbors (i.e. `neighbor - query`) are returned instead of their positions. If
+    /// the tree has a boxsize, these are the minimum image separations, i.e. they are computed
+    /// with the same image that produced the minimum image distance. See
+    /// [`Tree::query_nearest_k_separation`] for the metrics this holds for.
+    pub fn query_ball_point_separation(
+        &self,
+        query: &[T; D],
+        radius: T,
+    ) -> FnntwResult<QuerySeparationResult<T, D>, T> {
+        // Check for valid query point, wrapping it into the box
+        let query: &[NotNan<T>; D] = &self.check_query(query)?;
+
+        let result = self.query_ball_point_checked(query, radius)?;
+        let separations = self.separations(query, &result.1);
+
+        Ok((result.0, result.1, separations))
+    }
+
+    /// Returns the separation vectors from an already checked (and wrapped) `query` to the
+    /// points with the given `indices`.
+    fn separations(&self, query: &[NotNan<T>; D], indices: &[u64]) -> Vec<[T; D]> {
+        indices
+            .iter()
+            .map(|index| {
+                let neighbor = &self.input[*index as usize];
+                let mut separation = [T::zero(); D];
+                for (i, separation) in separation.iter_mut().enumerate() {
+                    *separation = neighbor[i] - *query[i];
+
+                    // Both points lie within the box, so the separation along a periodic axis
+                    // is within one boxsize of the minimum image. Nonperiodic axes have an
+                    // infinite boxsize and are never shifted. Folding every axis on its own
+                    // assumes an axis-separable metric, see `query_nearest_k_separation`.
+                    if let Some(ref boxsize) = self.boxsize {
+                        let side = *boxsize[i];
+                        let half = side / (T::one() + T::one());
+                        if *separation > half {
+                            *separation = *separation - side;
+                        } else if *separation < -half {
+                            *separation += side;
+                        }
+                    }
+                }
+                separation
+            })
+            .collect()
+    }
+}
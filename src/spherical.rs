@@ -0,0 +1,226 @@
+use std::fmt::Debug;
+
+use crate::{
+    owned::OwnedTree,
+    point::Float,
+    utils::{FnntwError, FnntwResult},
+    Tree,
+};
+
+/// The unit of the angles taken and returned by a [`SphericalTree`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AngleUnit {
+    Degrees,
+    Radians,
+}
+
+impl AngleUnit {
+    /// Converts an `angle` of this unit to radians.
+    pub fn to_radians<T: Float>(self, angle: T) -> T {
+        match self {
+            AngleUnit::Degrees => angle.to_radians(),
+            AngleUnit::Radians => angle,
+        }
+    }
+
+    /// Converts an `angle` in radians to this unit.
+    pub fn from_radians<T: Float>(self, angle: T) -> T {
+        match self {
+            AngleUnit::Degrees => angle.to_degrees(),
+            AngleUnit::Radians => angle,
+        }
+    }
+}
+
+/// Returns the unit vector pointing towards the spherical coordinates `(ra, dec)` in radians.
+pub fn unit_vector<T: Float>([ra, dec]: [T; 2]) -> [T; 3] {
+    let (sin_ra, cos_ra) = ra.sin_cos();
+    let (sin_dec, cos_dec) = dec.sin_cos();
+    [cos_dec * cos_ra, cos_dec * sin_ra, sin_dec]
+}
+
+/// Returns the angular separation (in radians) between the spherical coordinates `(ra, dec)`
+/// `a` and `b` (in radians), using the haversine formula which is accurate at small separations.
+pub fn haversine<T: Float>(a: &[T; 2], b: &[T; 2]) -> T {
+    let two = T::one() + T::one();
+    let hav_dec = ((b[1] - a[1]) / two).sin().powi(2);
+    let hav_ra = ((b[0] - a[0]) / two).sin().powi(2);
+    let hav = hav_dec + a[1].cos() * b[1].cos() * hav_ra;
+    two * hav.sqrt().min(T::one()).asin()
+}
+
+/// Converts the euclidean distance `chord` between two unit vectors to their angular
+/// separation in radians.
+pub fn chord_to_angle<T: Float>(chord: T) -> T {
+    let two = T::one() + T::one();
+    two * (chord / two).min(T::one()).asin()
+}
+
+/// Converts an angular separation in radians to the euclidean distance between two unit
+/// vectors. Separations larger than pi are clamped to pi, i.e. to a chord of two.
+pub fn angle_to_chord<T: Float>(angle: T) -> T {
+    let two = T::one() + T::one();
+    two * (angle.min(T::from(std::f64::consts::PI).unwrap()) / two).sin()
+}
+
+/// A kdtree for angular (great-circle) queries on the sphere. The tree is built on the unit
+/// vectors of the spherical coordinates `(ra, dec)` of the data, in which the nearest
+/// neighbors in angle are the nearest neighbors in euclidean (chord) distance.
+///
+/// All queries take coordinates and radii in the [`AngleUnit`] of the tree and return the
+/// angular separations of the neighbors in the same unit, which are computed exactly with
+/// the [`haversine`] formula. These are never squared, regardless of the `sqrt-dist2` feature,
+/// and the positions of the neighbors are not returned since they are in the input data.
+pub struct SphericalTree<'t, T: Float> {
+    /// Data used to build the tree
+    input: &'t [[T; 2]],
+
+    /// The unit of the angles of the input data, queries and results
+    unit: AngleUnit,
+
+    /// Tree built on (and owning) the unit vectors of the data
+    tree: OwnedTree<'t, T, 3>,
+}
+
+impl<'t, T: Float + Debug> SphericalTree<'t, T> {
+    /// Create a new [SphericalTree] from the spherical coordinates `(ra, dec)` in `unit`
+    /// using a nonparallel build.
+    pub fn new(
+        input: &'t [[T; 2]],
+        unit: AngleUnit,
+        leafsize: usize,
+    ) -> FnntwResult<SphericalTree<'t, T>, T> {
+        let unit_vectors = to_unit_vectors(input, unit)?;
+        let tree = OwnedTree::new(unit_vectors, leafsize)?;
+
+        Ok(SphericalTree { input, unit, tree })
+    }
+
+    /// Create a new [SphericalTree] from the spherical coordinates `(ra, dec)` in `unit`
+    /// using a parallel build. See [`Tree::new_parallel`].
+    pub fn new_parallel(
+        input: &'t [[T; 2]],
+        unit: AngleUnit,
+        leafsize: usize,
+        par_split_level: usize,
+    ) -> FnntwResult<SphericalTree<'t, T>, T> {
+        let unit_vectors = to_unit_vectors(input, unit)?;
+        let tree = OwnedTree::new_parallel(unit_vectors, leafsize, par_split_level)?;
+
+        Ok(SphericalTree { input, unit, tree })
+    }
+
+    /// Returns the unit of the angles of the input data, queries and results.
+    pub fn unit(&self) -> AngleUnit {
+        self.unit
+    }
+
+    /// Returns the spherical coordinates used to build the tree.
+    pub fn get_data(&self) -> &[[T; 2]] {
+        self.input
+    }
+
+    /// Returns the euclidean tree built on the unit vectors, e.g. for queries not provided by
+    /// [SphericalTree]. Its distances are chord lengths (see [`chord_to_angle`]).
+    pub fn unit_vector_tree(&self) -> &Tree<'_, T, 3> {
+        self.tree.tree()
+    }
+
+    /// Finds the nearest neighbor of the `query` in angle, returning its angular separation
+    /// and index.
+    pub fn query_nearest(&self, query: &[T; 2]) -> FnntwResult<(T, u64), T> {
+        let query = self.to_radians(query)?;
+        let index = self.tree.tree().query_nearest(&unit_vector(query))?.1;
+
+        Ok((self.separation(&query, index), index))
+    }
+
+    /// Finds the `k` nearest neighbors of the `query` in angle, returning their angular
+    /// separations and indices.
+    pub fn query_nearest_k(&self, query: &[T; 2], k: usize) -> FnntwResult<(Vec<T>, Vec<u64>), T> {
+        let query = self.to_radians(query)?;
+        let indices = self.tree.tree().query_nearest_k(&unit_vector(query), k)?.1;
+
+        Ok(self.with_separations(&query, indices))
+    }
+
+    /// Finds all points within an angular separation `radius` of the `query`, returning their
+    /// angular separations and indices sorted by separation.
+    pub fn query_ball_point(
+        &self,
+        query: &[T; 2],
+        radius: T,
+    ) -> FnntwResult<(Vec<T>, Vec<u64>), T> {
+        let query = self.to_radians(query)?;
+        let chord = self.chord(radius)?;
+        let tree = self.tree.tree();
+        let indices = tree.query_ball_point(&unit_vector(query), chord)?.1;
+
+        Ok(self.with_separations(&query, indices))
+    }
+
+    /// Counts the points within an angular separation `radius` of the `query`.
+    pub fn count_within(&self, query: &[T; 2], radius: T) -> FnntwResult<u64, T> {
+        let query = self.to_radians(query)?;
+        let chord = self.chord(radius)?;
+
+        self.tree.tree().count_within(&unit_vector(query), chord)
+    }
+
+    /// Checks the spherical coordinates `point` and converts them to radians.
+    fn to_radians(&self, point: &[T; 2]) -> FnntwResult<[T; 2], T> {
+        check_coordinates(point, self.unit)
+    }
+
+    /// Converts an angular `radius` to the chord length searched in the tree.
+    fn chord(&self, radius: T) -> FnntwResult<T, T> {
+        if radius.is_nan() || radius.is_infinite() || radius < T::zero() {
+            return Err(FnntwError::InvalidRadius);
+        }
+        Ok(angle_to_chord(self.unit.to_radians(radius)))
+    }
+
+    /// Returns the exact angular separation between `query` (in radians) and the point with
+    /// the given `index`, in the unit of the tree.
+    fn separation(&self, query: &[T; 2], index: u64) -> T {
+        let point = self.input[index as usize].map(|angle| self.unit.to_radians(angle));
+        self.unit.from_radians(haversine(query, &point))
+    }
+
+    /// Pairs the neighbors with the given `indices` with their angular separations.
+    fn with_separations(&self, query: &[T; 2], indices: Vec<u64>) -> (Vec<T>, Vec<u64>) {
+        let separations = indices
+            .iter()
+            .map(|index| self.separation(query, *index))
+            .collect();
+        (separations, indices)
+    }
+}
+
+/// Checks that the spherical coordinates `(ra, dec)` in `unit` are finite and that the
+/// declination is within `[-pi/2, pi/2]`, returning them in radians.
+fn check_coordinates<T: Float + Debug>(point: &[T; 2], unit: AngleUnit) -> FnntwResult<[T; 2], T> {
+    let max_dec = match unit {
+        AngleUnit::Degrees => T::from(90.0).unwrap(),
+        AngleUnit::Radians => T::from(std::f64::consts::FRAC_PI_2).unwrap(),
+    };
+    if point
+        .iter()
+        .any(|angle| angle.is_nan() || angle.is_infinite())
+        || point[1].abs() > max_dec
+    {
+        return Err(FnntwError::InvalidSphericalCoordinates);
+    }
+    Ok(point.map(|angle| unit.to_radians(angle)))
+}
+
+/// Checks the spherical coordinates `input` in `unit` and converts them to unit vectors.
+fn to_unit_vectors<T: Float + Debug>(
+    input: &[[T; 2]],
+    unit: AngleUnit,
+) -> FnntwResult<Vec<[T; 3]>, T> {
+    input
+        .iter()
+        .map(|point| check_coordinates(point, unit).map(unit_vector))
+        .collect()
+}
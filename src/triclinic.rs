@@ -0,0 +1,439 @@
+use std::fmt::Debug;
+
+use ordered_float::NotNan;
+
+use crate::{
+    point::Float,
+    query_k::container::Container,
+    utils::{
+        check_point, check_point_return, FnntwError, FnntwResult, QueryBallResult, QueryKResult,
+        QueryResult, QuerySeparationResult,
+    },
+    Tree,
+};
+
+/// A triclinic (sheared) periodic cell, spanned by `D` lattice vectors from the origin. A point
+/// `x` has fractional coordinates `f` with `x = sum_i f[i] * vectors[i]`, and lies within the
+/// cell if every fractional coordinate is within `[0, 1]`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TriclinicCell<T, const D: usize> {
+    /// Lattice vectors, one per row
+    vectors: [[T; D]; D],
+    /// Reciprocal vectors, one per row, such that `f[i] = reciprocal[i] . x`
+    reciprocal: [[T; D]; D],
+    /// Perpendicular distance between the opposite faces of the cell along every lattice vector
+    widths: [T; D],
+}
+
+impl<T: Float + Debug, const D: usize> TriclinicCell<T, D> {
+    /// Creates the cell spanned by the lattice `vectors` (one per row), which must be finite and
+    /// linearly independent.
+    pub fn new(vectors: &[[T; D]; D]) -> FnntwResult<Self, T> {
+        if vectors
+            .iter()
+            .flatten()
+            .any(|c| c.is_nan() || c.is_infinite())
+        {
+            return Err(FnntwError::InvalidLattice);
+        }
+
+        // The columns of the transpose of `vectors` are the lattice vectors, so its inverse
+        // maps cartesian to fractional coordinates
+        let mut transpose = [[T::zero(); D]; D];
+        for (i, vector) in vectors.iter().enumerate() {
+            for (j, component) in vector.iter().enumerate() {
+                transpose[j][i] = *component;
+            }
+        }
+        let reciprocal = invert(transpose).ok_or(FnntwError::InvalidLattice)?;
+        let widths = reciprocal
+            .map(|row| T::one() / row.iter().fold(T::zero(), |acc, x| acc + x.powi(2)).sqrt());
+
+        Ok(TriclinicCell {
+            vectors: *vectors,
+            reciprocal,
+            widths,
+        })
+    }
+
+    /// The lattice vectors, one per row.
+    pub fn vectors(&self) -> &[[T; D]; D] {
+        &self.vectors
+    }
+
+    /// The perpendicular distance between the opposite faces of the cell along every lattice
+    /// vector. Half of the smallest of these is the largest radius of a periodic query.
+    pub fn widths(&self) -> &[T; D] {
+        &self.widths
+    }
+
+    /// Returns the fractional coordinates of the cartesian `point`.
+    pub fn fractional(&self, point: &[T; D]) -> [T; D] {
+        self.reciprocal.map(|row| {
+            row.iter()
+                .zip(point)
+                .fold(T::zero(), |acc, (r, x)| acc + *r * *x)
+        })
+    }
+
+    /// Returns the cartesian coordinates of the `fractional` coordinates.
+    pub fn cartesian(&self, fractional: &[T; D]) -> [T; D] {
+        let mut point = [T::zero(); D];
+        for (f, vector) in fractional.iter().zip(&self.vectors) {
+            for (x, component) in point.iter_mut().zip(vector) {
+                *x += *f * *component;
+            }
+        }
+        point
+    }
+
+    /// Returns the image of `point` within the cell. Points already within the cell are
+    /// returned unchanged.
+    pub fn wrap(&self, point: &[T; D]) -> [T; D] {
+        let fractional = self.fractional(point);
+        if fractional.iter().all(|f| *f >= T::zero() && *f < T::one()) {
+            *point
+        } else {
+            self.cartesian(&fractional.map(|f| f - f.floor()))
+        }
+    }
+
+    /// Returns the minimum image separation from a `query` within the cell to a `point` within
+    /// the cell, i.e. the shortest `point - image` over the images of the query shifted by at
+    /// most one lattice vector along every axis, which are the images searched by queries.
+    fn separation(&self, query: &[NotNan<T>; D], point: &[T; D]) -> [T; D] {
+        let real: [T; D] = std::array::from_fn(|axis| point[axis] - *query[axis]);
+        let dist2 = |x: &[T; D]| x.iter().fold(T::zero(), |acc, x| acc + x.powi(2));
+
+        let (mut best, mut best_dist2) = (real, dist2(&real));
+        for image in 0..3_usize.pow(D as u32) {
+            // Shift along every lattice vector in the form of -1, 0 or 1
+            let shifts: [T; D] = std::array::from_fn(|idx| {
+                T::from((image / 3_usize.pow(idx as u32) % 3) as i8 - 1).unwrap()
+            });
+
+            // The image of the query is shifted by minus the offset, see `images_within`
+            let offset = self.cartesian(&shifts);
+            let separation: [T; D] = std::array::from_fn(|axis| real[axis] + offset[axis]);
+            if dist2(&separation) < best_dist2 {
+                best_dist2 = dist2(&separation);
+                best = separation;
+            }
+        }
+
+        best
+    }
+
+    /// Given a `query` within the cell, returns all of the (non-real) images of `query` shifted
+    /// by at most one lattice vector along every axis, whose associated faces of the cell are
+    /// closer than the squared distance `dist2` to the query.
+    ///
+    /// Along every lattice vector that the query is shifted by, the distance to any point in
+    /// the cell is at least the distance from the query to the corresponding face, so an image
+    /// can be skipped if any of these is at least `dist2`. Shifting by at most one lattice
+    /// vector along every axis is sufficient so long as `dist2` does not exceed the square of
+    /// the smallest width of the cell.
+    fn images_within(&self, query: &[NotNan<T>; D], dist2: T) -> Vec<[NotNan<T>; D]> {
+        let fractional = self.fractional(&query.map(|x| *x));
+
+        // Squared distance to the lower and upper face along every lattice vector
+        let face_dist2: [(T, T); D] = std::array::from_fn(|i| {
+            let lower = fractional[i] * self.widths[i];
+            let upper = (T::one() - fractional[i]) * self.widths[i];
+            (lower.powi(2), upper.powi(2))
+        });
+
+        let mut images_to_check = Vec::new();
+        for image in 0..3_usize.pow(D as u32) {
+            // Shift along every lattice vector in the form of -1, 0 or 1
+            let shifts: [i8; D] =
+                std::array::from_fn(|idx| (image / 3_usize.pow(idx as u32) % 3) as i8 - 1);
+            if shifts.iter().all(|shift| *shift == 0) {
+                continue;
+            }
+
+            // Distance to the faces (or their intersection) of this image
+            let dist_to_faces = shifts
+                .iter()
+                .zip(&face_dist2)
+                .map(|(shift, (lower, upper))| match shift {
+                    1 => *upper,
+                    -1 => *lower,
+                    _ => T::zero(),
+                })
+                .fold(T::zero(), |acc, x| acc.max(x));
+
+            if dist_to_faces < dist2 {
+                // Points beyond the upper face are the images of points near the lower face
+                // shifted by a lattice vector, so the query is shifted the opposite way
+                let image_to_check: [NotNan<T>; D] = std::array::from_fn(|axis| {
+                    let offset = shifts
+                        .iter()
+                        .zip(&self.vectors)
+                        .fold(T::zero(), |acc, (shift, vector)| {
+                            acc + T::from(*shift).unwrap() * vector[axis]
+                        });
+                    query[axis] - offset
+                });
+                images_to_check.push(image_to_check);
+            }
+        }
+
+        images_to_check
+    }
+}
+
+/// A kdtree for periodic queries in a [`TriclinicCell`], e.g. for molecular dynamics or
+/// Lees-Edwards shear simulations. The tree is built on the cartesian data, which must lie
+/// within the cell, and the minimum image distance is found by also searching the images of
+/// the query shifted by the lattice vectors.
+///
+/// Queries outside of the cell are wrapped into it. The nearest neighbors are exact so long as
+/// they are closer than half of the smallest width of the cell (see
+/// [`TriclinicCell::widths`]). Likewise, the radius of ball queries must be smaller than
+/// half of the smallest width.
+pub struct TriclinicTree<'t, T: Float, const D: usize> {
+    /// The periodic cell
+    cell: TriclinicCell<T, D>,
+
+    /// Tree built on the data
+    tree: Tree<'t, T, D>,
+}
+
+impl<'t, T: Float + Debug, const D: usize> TriclinicTree<'t, T, D> {
+    /// Create a new [TriclinicTree] for the cell spanned by the lattice `vectors` (one per row)
+    /// using a nonparallel build.
+    pub fn new(
+        input: &'t [[T; D]],
+        vectors: &[[T; D]; D],
+        leafsize: usize,
+    ) -> FnntwResult<TriclinicTree<'t, T, D>, T> {
+        let cell = TriclinicCell::new(vectors)?;
+        check_within(&cell, input)?;
+        let tree = Tree::new(input, leafsize)?;
+
+        Ok(TriclinicTree { cell, tree })
+    }
+
+    /// Create a new [TriclinicTree] for the cell spanned by the lattice `vectors` (one per row)
+    /// using a parallel build. See [`Tree::new_parallel`].
+    pub fn new_parallel(
+        input: &'t [[T; D]],
+        vectors: &[[T; D]; D],
+        leafsize: usize,
+        par_split_level: usize,
+    ) -> FnntwResult<TriclinicTree<'t, T, D>, T> {
+        let cell = TriclinicCell::new(vectors)?;
+        check_within(&cell, input)?;
+        let tree = Tree::new_parallel(input, leafsize, par_split_level)?;
+
+        Ok(TriclinicTree { cell, tree })
+    }
+
+    /// Returns the periodic cell.
+    pub fn cell(&self) -> &TriclinicCell<T, D> {
+        &self.cell
+    }
+
+    pub fn get_data(&self) -> &[[T; D]] {
+        self.tree.get_data()
+    }
+
+    /// Returns the nonperiodic tree built on the data.
+    pub fn tree(&self) -> &Tree<'t, T, D> {
+        &self.tree
+    }
+
+    /// Finds the nearest neighbor of the `query` using the minimum image distance.
+    pub fn query_nearest<'q>(&'q self, query: &[T; D]) -> FnntwResult<QueryResult<'q, T, D>, T> {
+        let result = self.query_nearest_k(query, 1)?;
+        let index = result.1[0];
+
+        #[cfg(feature = "no-position")]
+        return Ok((result.0[0], index));
+        #[cfg(not(feature = "no-position"))]
+        {
+            // safety: the data was checked when building the tree
+            let position = unsafe {
+                std::mem::transmute::<&[T; D], &[NotNan<T>; D]>(&self.get_data()[index as usize])
+            };
+            Ok((result.0[0], index, position))
+        }
+    }
+
+    /// Finds the `k` nearest neighbors of the `query` using the minimum image distance.
+    pub fn query_nearest_k(
+        &self,
+        query: &[T; D],
+        k: usize,
+    ) -> FnntwResult<QueryKResult<'t, T, D>, T> {
+        let query = &self.wrap_query(query)?;
+        if k == 0 {
+            return Err(FnntwError::InvalidK);
+        }
+
+        Ok(self.nearest_k(query, k))
+    }
+
+    /// Same as [`TriclinicTree::query_nearest_k`], except that the minimum image separation
+    /// vectors from the query to its neighbors (i.e. `neighbor - query`) are returned instead
+    /// of their positions.
+    pub fn query_nearest_k_separation(
+        &self,
+        query: &[T; D],
+        k: usize,
+    ) -> FnntwResult<QuerySeparationResult<T, D>, T> {
+        let query = &self.wrap_query(query)?;
+        if k == 0 {
+            return Err(FnntwError::InvalidK);
+        }
+
+        let result = self.nearest_k(query, k);
+        let data = self.get_data();
+        let separations = result
+            .1
+            .iter()
+            .map(|index| self.cell.separation(query, &data[*index as usize]))
+            .collect();
+
+        Ok((result.0, result.1, separations))
+    }
+
+    /// Finds the `k` nearest neighbors of an already checked and wrapped `query`.
+    fn nearest_k(&self, query: &[NotNan<T>; D], k: usize) -> QueryKResult<'t, T, D> {
+        // First get real image result
+        let mut container = Container::new(k.min(self.get_data().len()));
+        let mut points_to_check = Vec::with_capacity(self.tree.height_hint);
+        self.tree
+            .fill_nearest_k(query, &mut container, &mut points_to_check);
+
+        // Then check all images closer than the current kth nearest neighbor
+        for image in self.cell.images_within(query, *container.best_dist2()) {
+            self.tree
+                .search_nearest_k(&image, &mut container, &mut points_to_check);
+        }
+
+        container.index(self.tree.start(), &self.tree.metric)
+    }
+
+    /// Finds all points within `radius` of the `query` using the minimum image distance,
+    /// sorted by distance. The radius must be smaller than half of the smallest width of the
+    /// cell.
+    pub fn query_ball_point(
+        &self,
+        query: &[T; D],
+        radius: T,
+    ) -> FnntwResult<QueryBallResult<'t, T, D>, T> {
+        let query = &self.wrap_query(query)?;
+        let radius2 = self.check_radius(radius)?;
+
+        // Since the radius is smaller than half of the smallest width, no point can be
+        // found in more than one image
+        let mut neighbors = Vec::new();
+        let mut nodes_to_check = Vec::with_capacity(self.tree.height_hint);
+        self.tree
+            .check_ball(query, radius2, &mut neighbors, &mut nodes_to_check);
+        for image in self.cell.images_within(query, radius2) {
+            self.tree
+                .check_ball(&image, radius2, &mut neighbors, &mut nodes_to_check);
+        }
+
+        Ok(self.tree.ball_result(&mut neighbors))
+    }
+
+    /// Counts the points within `radius` of the `query` using the minimum image distance. The
+    /// radius must be smaller than half of the smallest width of the cell.
+    pub fn count_within(&self, query: &[T; D], radius: T) -> FnntwResult<u64, T> {
+        let query = &self.wrap_query(query)?;
+        let radius2 = self.check_radius(radius)?;
+
+        let mut nodes_to_check = Vec::with_capacity(self.tree.height_hint);
+        let mut count = self.tree.count_ball(query, radius2, &mut nodes_to_check);
+        for image in self.cell.images_within(query, radius2) {
+            count += self.tree.count_ball(&image, radius2, &mut nodes_to_check);
+        }
+
+        Ok(count)
+    }
+
+    /// Checks the `query` and wraps it into the cell.
+    fn wrap_query(&self, query: &[T; D]) -> FnntwResult<[NotNan<T>; D], T> {
+        check_point(query)?;
+        Ok(*check_point_return(&self.cell.wrap(query))?)
+    }
+
+    /// Checks that the `radius` of a query is finite, nonnegative and smaller than half of the
+    /// smallest width of the cell, returning the squared radius.
+    fn check_radius(&self, radius: T) -> FnntwResult<T, T> {
+        if radius.is_nan() || radius.is_infinite() || radius < T::zero() {
+            return Err(FnntwError::InvalidRadius);
+        }
+        let two = T::one() + T::one();
+        if self.cell.widths.iter().any(|width| two * radius >= *width) {
+            return Err(FnntwError::LargeRadiusPeriodicQuery);
+        }
+        Ok(radius.powi(2))
+    }
+}
+
+/// Checks that every point of `input` lies within the `cell`.
+fn check_within<T: Float + Debug, const D: usize>(
+    cell: &TriclinicCell<T, D>,
+    input: &[[T; D]],
+) -> FnntwResult<(), T> {
+    for point in input {
+        let fractional = cell.fractional(point);
+        if fractional.iter().any(|f| *f < T::zero()) {
+            return Err(FnntwError::NegativeDataPeriodicQuery);
+        } else if fractional.iter().any(|f| *f > T::one()) {
+            return Err(FnntwError::SmallBoxsize);
+        }
+    }
+    Ok(())
+}
+
+/// Inverts `matrix` by Gauss-Jordan elimination with partial pivoting, returning `None` if it
+/// is singular.
+fn invert<T: Float, const D: usize>(mut matrix: [[T; D]; D]) -> Option<[[T; D]; D]> {
+    let mut inverse = [[T::zero(); D]; D];
+    for (i, row) in inverse.iter_mut().enumerate() {
+        row[i] = T::one();
+    }
+
+    for column in 0..D {
+        // Swap the largest remaining pivot into place
+        let pivot_row = (column..D).max_by(|a, b| {
+            matrix[*a][column]
+                .abs()
+                .partial_cmp(&matrix[*b][column].abs())
+                .expect("lattice vectors are finite")
+        })?;
+        if matrix[pivot_row][column] == T::zero() {
+            return None;
+        }
+        matrix.swap(column, pivot_row);
+        inverse.swap(column, pivot_row);
+
+        // Normalize the pivot row and eliminate the column from every other row
+        let pivot = matrix[column][column];
+        let (pivot_matrix_row, pivot_inverse_row) = (
+            matrix[column].map(|x| x / pivot),
+            inverse[column].map(|x| x / pivot),
+        );
+        matrix[column] = pivot_matrix_row;
+        inverse[column] = pivot_inverse_row;
+        for row in (0..D).filter(|row| *row != column) {
+            let factor = matrix[row][column];
+            for j in 0..D {
+                matrix[row][j] = matrix[row][j] - factor * pivot_matrix_row[j];
+                inverse[row][j] = inverse[row][j] - factor * pivot_inverse_row[j];
+            }
+        }
+    }
+
+    inverse
+        .iter()
+        .flatten()
+        .all(|x| x.is_finite())
+        .then_some(inverse)
+}
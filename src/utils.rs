@@ -1,6 +1,6 @@
 use std::fmt::Debug;
 
-use crate::point::Float;
+use crate::{metric::Metric, point::Float};
 use ordered_float::NotNan;
 use thiserror::Error;
 
@@ -20,6 +20,23 @@ pub type QueryKResult<'t, T, const D: usize> = (Vec<T>, Vec<u64>);
 #[cfg(not(feature = "no-position"))]
 pub type QueryKResult<'t, T, const D: usize> = (Vec<T>, Vec<u64>, Vec<[NotNan<T>; D]>);
 
+#[cfg(feature = "no-position")]
+pub type QueryBallResult<'t, T, const D: usize> = (Vec<T>, Vec<u64>);
+#[cfg(not(feature = "no-position"))]
+pub type QueryBallResult<'t, T, const D: usize> = (Vec<T>, Vec<u64>, Vec<[NotNan<T>; D]>);
+
+/// Compressed sparse row (CSR) result of a batched ball query. The neighbors of query `i`
+/// are found at `offsets[i]..offsets[i + 1]` of the remaining vectors.
+#[cfg(feature = "no-position")]
+pub type QueryBallCsrResult<'t, T, const D: usize> = (Vec<usize>, Vec<T>, Vec<u64>);
+#[cfg(not(feature = "no-position"))]
+pub type QueryBallCsrResult<'t, T, const D: usize> =
+    (Vec<usize>, Vec<T>, Vec<u64>, Vec<[NotNan<T>; D]>);
+
+/// Compressed sparse row (CSR) result of a batched kNN query with a different k for every
+/// query, in the same format as [`QueryBallCsrResult`].
+pub type QueryKCsrResult<'t, T, const D: usize> = QueryBallCsrResult<'t, T, D>;
+
 #[cfg(feature = "no-index")]
 pub type QueryKAxisResult<'t, T, const D: usize> = (Vec<T>, Vec<T>);
 #[cfg(all(feature = "no-position", not(feature = "no-index")))]
@@ -27,6 +44,10 @@ pub type QueryKAxisResult<'t, T, const D: usize> = (Vec<T>, Vec<T>, Vec<u64>);
 #[cfg(not(feature = "no-position"))]
 pub type QueryKAxisResult<'t, T, const D: usize> = (Vec<T>, Vec<T>, Vec<u64>, Vec<[NotNan<T>; D]>);
 
+/// Result of a query returning the separation vectors from the query to its neighbors in
+/// place of their positions, i.e. (`distances`, `indices`, `separations`).
+pub type QuerySeparationResult<T, const D: usize> = (Vec<T>, Vec<u64>, Vec<[T; D]>);
+
 pub(super) fn check_data<'d, T: Float + Debug, const D: usize>(
     data: &'d [[T; D]],
 ) -> FnntwResult<(), T> {
@@ -71,6 +92,50 @@ pub(crate) fn check_point<'p, T: Float + Debug, const D: usize>(
     Ok(())
 }
 
+/// Checks that the `radius` of a query is finite and nonnegative, returning the radius as a
+/// reduced distance of the `metric` (e.g. the squared radius for [`SquaredEuclidean`]).
+/// If a `boxsize` is given, also checks that the radius is smaller than the distance spanned by
+/// half of the boxsize along every dimension, as required by the periodic image search. At
+/// exactly half of the boxsize, a point could be found in both the real and a shifted image.
+///
+/// [`SquaredEuclidean`]: crate::metric::SquaredEuclidean
+pub(crate) fn check_radius_return<T: Float + Debug, const D: usize, M: Metric<T, D>>(
+    radius: T,
+    boxsize: Option<&[NotNan<T>; D]>,
+    metric: &M,
+) -> FnntwResult<T, T> {
+    if radius.is_nan() || radius.is_infinite() || radius < T::zero() {
+        return Err(FnntwError::InvalidRadius);
+    }
+    let reduced = metric.to_reduced(radius);
+    if let Some(boxsize) = boxsize {
+        let two = T::one() + T::one();
+        if boxsize
+            .iter()
+            .enumerate()
+            .any(|(axis, component)| metric.axis_dist(axis, **component / two) <= reduced)
+        {
+            return Err(FnntwError::LargeRadiusPeriodicQuery);
+        }
+    }
+    Ok(reduced)
+}
+
+/// Checks that the `epsilon` of an approximate query is finite and nonnegative, returning the
+/// factor by which reduced distances to subtrees are scaled when pruning, e.g. `(1 + epsilon)^2`
+/// for [`SquaredEuclidean`].
+///
+/// [`SquaredEuclidean`]: crate::metric::SquaredEuclidean
+pub(crate) fn check_epsilon_return<T: Float + Debug, const D: usize, M: Metric<T, D>>(
+    epsilon: T,
+    metric: &M,
+) -> FnntwResult<T, T> {
+    if epsilon.is_nan() || epsilon.is_infinite() || epsilon < T::zero() {
+        return Err(FnntwError::InvalidEpsilon);
+    }
+    Ok(metric.to_reduced(T::one() + epsilon) / metric.to_reduced(T::one()))
+}
+
 #[derive(Debug, Error)]
 pub enum FnntwError<T: Float + Debug> {
     #[error("Invalid input data was detected: {data_point:?}")]
@@ -82,43 +147,85 @@ pub enum FnntwError<T: Float + Debug> {
     #[error("Invalid boxsize: data does not fit in the specified box")]
     SmallBoxsize,
 
-    #[error("Invalid boxsize: contains nan, inf, or subnormal float")]
+    #[error("Invalid boxsize: contains nan, inf, or subnormal float, or a nonpositive side")]
     InvalidBoxsize,
 
     #[error("Requested an axis that does not exist (incorrect dimensionality)")]
     InvalidAxis,
 
     #[error(
-        "At least one of your data points has a negative component. \
-             To use periodic queries, shift your data bounding box to start \
-             at the origin"
+        "At least one of your data points lies below the lower corner of the periodic box, \
+             which is the origin unless given. To use periodic queries, shift your data or \
+             give the corners of the box"
     )]
     NegativeDataPeriodicQuery,
-}
 
-#[cfg(feature = "sqrt-dist2")]
-pub(crate) fn process_result<'t, T: Float, const D: usize>(
-    mut result: QueryResult<'t, T, D>,
-) -> QueryResult<'t, T, D> {
-    result.0 = result.0.sqrt();
-    result
-}
+    #[error("Invalid radius: must be finite and nonnegative")]
+    InvalidRadius,
 
-#[cfg(not(feature = "sqrt-dist2"))]
-#[inline(always)]
-pub(crate) fn process_result<'t, T: Float, const D: usize>(
-    result: QueryResult<'t, T, D>,
-) -> QueryResult<'t, T, D> {
-    result
-}
+    #[error(
+        "Invalid radius: periodic queries require a radius smaller than the distance \
+             spanned by half the boxsize along every axis"
+    )]
+    LargeRadiusPeriodicQuery,
 
-#[cfg(feature = "sqrt-dist2")]
-pub(crate) fn process_dist2<T: Float>(dist2: T) -> T {
-    dist2.sqrt()
-}
+    #[error("The number of radii does not match the number of queries")]
+    RadiiLengthMismatch,
+
+    #[error("Invalid bin edges: must be at least two finite, nonnegative, increasing values")]
+    InvalidBinEdges,
+
+    #[error("The correlation function requires at least two points in every catalog")]
+    TooFewPoints,
+
+    #[error("The trees do not have the same periodic box")]
+    BoxsizeMismatch,
+
+    #[error("The number of weights does not match the number of data points")]
+    WeightsLengthMismatch,
+
+    #[error("This operation requires a tree with a boxsize along every axis")]
+    MissingBoxsize,
+
+    #[error("Invalid k: must be nonzero and no larger than the number of data points")]
+    InvalidK,
+
+    #[error("Invalid distances: must be at least one finite, nonnegative, increasing value")]
+    InvalidDistances,
+
+    #[error(
+        "Invalid ranks: must be nonempty, increasing, and between one \
+             and the number of data points"
+    )]
+    InvalidRanks,
+
+    #[error("Requested a data index that does not exist")]
+    InvalidIndex,
+
+    #[error("Invalid epsilon: must be finite and nonnegative")]
+    InvalidEpsilon,
+
+    #[error("The number of k values does not match the number of queries")]
+    KLengthMismatch,
+
+    #[error("Invalid range: bounds must be finite, with lower no larger than upper")]
+    InvalidRange,
+
+    #[error("Invalid Minkowski p: must be finite and at least one")]
+    InvalidMinkowskiP,
+
+    #[error("Invalid metric weights: must be finite and positive")]
+    InvalidMetricWeights,
+
+    #[error("Invalid covariance: must be finite and positive definite")]
+    InvalidCovariance,
+
+    #[error("Invalid spherical coordinates: must be finite, with a declination of at most 90 degrees in magnitude")]
+    InvalidSphericalCoordinates,
+
+    #[error("Invalid vector: cosine similarity is undefined for vectors with zero norm")]
+    ZeroNormVector,
 
-#[cfg(not(feature = "sqrt-dist2"))]
-#[inline(always)]
-pub(crate) fn process_dist2<T: Float>(dist2: T) -> T {
-    dist2
+    #[error("Invalid lattice: lattice vectors must be finite and linearly independent")]
+    InvalidLattice,
 }
@@ -0,0 +1,109 @@
+use fnntw::Tree;
+use rand::{rngs::ThreadRng, Rng};
+use std::error::Error;
+
+const NDATA: usize = 1_000;
+const BOXSIZE: [f64; 3] = [1.0; 3];
+const D: usize = 3;
+const K: usize = 12;
+
+#[test]
+fn test_all_nearest_k() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random data
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+
+    // Check against brute force, with and without pbcs, for several leafsizes
+    for leafsize in [4, 32] {
+        let tree = Tree::<'_, _, D>::new(&data, leafsize)?;
+        check_all_nearest_k(&tree, None)?;
+        let tree = tree.with_boxsize(&BOXSIZE)?;
+        check_all_nearest_k(&tree, Some(&BOXSIZE))?;
+    }
+
+    // Fewer points than k
+    let small = &data[..5];
+    let tree = Tree::<'_, _, D>::new(small, 8)?;
+    assert_eq!(tree.all_nearest_k(K, false)?.0.len(), 5 * 5);
+    assert_eq!(tree.all_nearest_k(K, true)?.0.len(), 5 * 4);
+    assert!(tree.all_nearest_k(0, false).is_err());
+
+    Ok(())
+}
+
+fn check_all_nearest_k(
+    tree: &Tree<'_, f64, D>,
+    boxsize: Option<&[f64; D]>,
+) -> Result<(), Box<dyn Error>> {
+    let data = tree.get_data();
+
+    for exclude_self in [false, true] {
+        let result = tree.all_nearest_k(K, exclude_self)?;
+        assert_eq!(result.0.len(), NDATA * K);
+        assert_eq!(result.1.len(), NDATA * K);
+
+        for (i, q) in data.iter().enumerate() {
+            let row = i * K..(i + 1) * K;
+            let indices = &result.1[row.clone()];
+
+            // The point itself is the nearest neighbor unless it is excluded
+            if exclude_self {
+                assert!(!indices.contains(&(i as u64)));
+            } else {
+                assert_eq!(result.0[i * K], 0.0);
+            }
+
+            let exclude = exclude_self.then_some(i);
+            let expected = brute_force_distances(q, data, exclude, boxsize);
+            #[cfg(feature = "sqrt-dist2")]
+            let expected: Vec<f64> = expected.into_iter().map(f64::sqrt).collect();
+            for (r, e) in result.0[row.clone()].iter().zip(&expected) {
+                assert!((r - e).abs() < 1e-12, "{r} != {e}");
+            }
+
+            // Indices and positions are consistent with the distances
+            #[cfg(not(feature = "no-position"))]
+            for (index, position) in indices.iter().zip(&result.2[row]) {
+                assert_eq!(
+                    data[*index as usize].map(|x| x.to_bits()),
+                    position.map(|x| x.to_bits())
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn random_point<const D: usize>(rng: &mut ThreadRng) -> [f64; D] {
+    [(); D].map(|_| rng.gen())
+}
+
+fn brute_force_distances(
+    q: &[f64; D],
+    data: &[[f64; D]],
+    exclude: Option<usize>,
+    boxsize: Option<&[f64; D]>,
+) -> Vec<f64> {
+    let mut all: Vec<f64> = data
+        .iter()
+        .enumerate()
+        .filter(|(j, _)| Some(*j) != exclude)
+        .map(|(_, d)| {
+            let mut dist2 = 0.0;
+            for idx in 0..D {
+                let mut dx = (q[idx] - d[idx]).abs();
+                if let Some(boxsize) = boxsize {
+                    dx = dx.min(boxsize[idx] - dx);
+                }
+                dist2 += dx * dx;
+            }
+            dist2
+        })
+        .collect();
+    all.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    all.truncate(K);
+    all
+}
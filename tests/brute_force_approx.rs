@@ -0,0 +1,127 @@
+use fnntw::{utils::FnntwError, Tree};
+use rand::{rngs::ThreadRng, Rng};
+use std::error::Error;
+
+const NDATA: usize = 2_000;
+const NQUERY: usize = 500;
+const BOXSIZE: [f64; 3] = [1.0; 3];
+const D: usize = 3;
+const K: usize = 8;
+const EPSILONS: [f64; 3] = [0.0, 0.5, 2.0];
+
+#[test]
+fn test_brute_force_approx() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random data, query
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+    let query: Vec<[f64; D]> = (0..NQUERY).map(|_| random_point(&mut rng)).collect();
+
+    // Check against brute force, with and without pbcs
+    let tree = Tree::<'_, _, D>::new(&data, 8)?;
+    check_approx(&tree, &query, None)?;
+    let tree = tree.with_boxsize(&BOXSIZE)?;
+    check_approx(&tree, &query, Some(&BOXSIZE))?;
+
+    // Invalid epsilon
+    assert!(tree.query_nearest_approx(&query[0], -1.0).is_err());
+    assert!(tree.query_nearest_k_approx(&query[0], K, f64::NAN).is_err());
+
+    // Invalid k
+    assert!(matches!(
+        tree.query_nearest_k_approx(&query[0], 0, 0.5),
+        Err(FnntwError::InvalidK)
+    ));
+    #[cfg(feature = "parallel")]
+    assert!(matches!(
+        tree.query_nearest_k_parallel_approx(&query, 0, 0.5),
+        Err(FnntwError::InvalidK)
+    ));
+
+    Ok(())
+}
+
+fn check_approx(
+    tree: &Tree<'_, f64, D>,
+    query: &[[f64; D]],
+    boxsize: Option<&[f64; D]>,
+) -> Result<(), Box<dyn Error>> {
+    let data = tree.get_data();
+
+    for epsilon in EPSILONS {
+        #[cfg(feature = "parallel")]
+        let par_result = tree.query_nearest_k_parallel_approx(query, K, epsilon)?;
+
+        for (i, q) in query.iter().enumerate() {
+            let expected = brute_force_distances(q, data, boxsize);
+
+            // Nearest neighbor
+            let result = tree.query_nearest_approx(q, epsilon)?;
+            check_bound(result.0, expected[0], epsilon);
+            assert!((distance(q, &data[result.1 as usize], boxsize) - result.0).abs() < 1e-12);
+
+            // k nearest neighbors
+            let result = tree.query_nearest_k_approx(q, K, epsilon)?;
+            assert_eq!(result.0.len(), K);
+            for (r, e) in result.0.iter().zip(&expected) {
+                check_bound(*r, *e, epsilon);
+            }
+            for (r, index) in result.0.iter().zip(&result.1) {
+                assert!((distance(q, &data[*index as usize], boxsize) - r).abs() < 1e-12);
+            }
+
+            #[cfg(feature = "parallel")]
+            {
+                let row = i * K..(i + 1) * K;
+                assert_eq!(result.0, &par_result.0[row.clone()]);
+                assert_eq!(result.1, &par_result.1[row]);
+            }
+            #[cfg(not(feature = "parallel"))]
+            let _ = i;
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that the approximate distance is no closer than the exact one, and no farther than
+/// `1 + epsilon` times the exact one.
+fn check_bound(approx: f64, exact: f64, epsilon: f64) {
+    #[cfg(feature = "sqrt-dist2")]
+    let factor = 1.0 + epsilon;
+    #[cfg(not(feature = "sqrt-dist2"))]
+    let factor = (1.0 + epsilon) * (1.0 + epsilon);
+
+    assert!(approx >= exact - 1e-12, "{approx} < {exact}");
+    assert!(
+        approx <= factor * exact + 1e-12,
+        "{approx} > {factor} * {exact}"
+    );
+}
+
+fn random_point<const D: usize>(rng: &mut ThreadRng) -> [f64; D] {
+    [(); D].map(|_| rng.gen())
+}
+
+/// The distance as returned by the tree, i.e. squared unless `sqrt-dist2` is enabled
+fn distance(q: &[f64; D], d: &[f64; D], boxsize: Option<&[f64; D]>) -> f64 {
+    let mut dist2 = 0.0;
+    for idx in 0..D {
+        let mut dx = (q[idx] - d[idx]).abs();
+        if let Some(boxsize) = boxsize {
+            dx = dx.min(boxsize[idx] - dx);
+        }
+        dist2 += dx * dx;
+    }
+    #[cfg(feature = "sqrt-dist2")]
+    let dist2 = dist2.sqrt();
+    dist2
+}
+
+fn brute_force_distances(q: &[f64; D], data: &[[f64; D]], boxsize: Option<&[f64; D]>) -> Vec<f64> {
+    let mut all: Vec<f64> = data.iter().map(|d| distance(q, d, boxsize)).collect();
+    all.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    all.truncate(K);
+    all
+}
@@ -0,0 +1,155 @@
+use fnntw::{
+    distance::squared_euclidean,
+    point::Float,
+    utils::{FnntwError, QueryBallResult},
+    Tree,
+};
+use ordered_float::NotNan;
+use rand::{rngs::ThreadRng, Rng};
+use std::error::Error;
+
+const NDATA: usize = 1_000;
+const NQUERY: usize = 1_000;
+const BOXSIZE: [f64; 3] = [1.0; 3];
+const D: usize = 3;
+const RADIUS: f64 = 0.1;
+
+#[test]
+fn test_brute_force_ball() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random data, query
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+    let query: Vec<[f64; D]> = (0..NQUERY).map(|_| random_point(&mut rng)).collect();
+
+    // Construct tree
+    let tree = Tree::<'_, _, D>::new_parallel(&data, 4, 1)?;
+
+    // Query tree and brute force check results
+    for q in &query {
+        let result = tree.query_ball_point(q, RADIUS)?;
+        let expected = brute_force_ball(q, &data, RADIUS, None);
+        assert_eq!(result, expected);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_brute_force_periodic_ball() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random data, query
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+    let query: Vec<[f64; D]> = (0..NQUERY).map(|_| random_point(&mut rng)).collect();
+
+    // Construct tree
+    let tree = Tree::<'_, _, D>::new_parallel(&data, 4, 1)?.with_boxsize(&BOXSIZE)?;
+
+    // Query tree and brute force check results
+    for q in &query {
+        let result = tree.query_ball_point(q, RADIUS)?;
+        let expected = brute_force_ball(q, &data, RADIUS, Some(&BOXSIZE));
+        assert_eq!(result, expected);
+    }
+
+    // Radius larger than half the boxsize is not supported
+    assert!(tree.query_ball_point(&query[0], 0.6).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_periodic_ball_half_boxsize() -> Result<(), Box<dyn Error>> {
+    // A point at exactly half the boxsize from the query is within the radius of both the real
+    // query and its shifted image
+    let boxsize = [1.0; 2];
+    let data = [[0.75, 0.5], [0.1, 0.1]];
+    let query = [0.25, 0.5];
+    let tree = Tree::<'_, _, 2>::new(&data, 4)?.with_boxsize(&boxsize)?;
+
+    // So a radius of half the boxsize is not supported
+    assert!(matches!(
+        tree.query_ball_point(&query, 0.5),
+        Err(FnntwError::LargeRadiusPeriodicQuery)
+    ));
+
+    // Just below it, every point is found once
+    let radius = 0.5 - 1e-12;
+    let result = tree.query_ball_point(&query, radius)?;
+    assert_eq!(
+        result,
+        brute_force_ball(&query, &data, radius, Some(&boxsize))
+    );
+    assert_eq!(result.1, vec![1]);
+
+    Ok(())
+}
+
+fn random_point<const D: usize>(rng: &mut ThreadRng) -> [f64; D] {
+    [(); D].map(|_| rng.gen())
+}
+
+fn brute_force_ball<'d, T: Float, const D: usize>(
+    q: &[T; D],
+    data: &'d [[T; D]],
+    radius: T,
+    boxsize: Option<&[T; D]>,
+) -> QueryBallResult<'d, T, D> {
+    // No need for nan checks here
+    let q: &[NotNan<T>; D] = unsafe { std::mem::transmute(q) };
+    let data: &'d [[NotNan<T>; D]] = unsafe { std::mem::transmute(data) };
+
+    let mut all = Vec::with_capacity(data.len());
+
+    for (d, i) in data.iter().zip(0..) {
+        let dist = match boxsize {
+            // Minimum image distance, using the same closest image as the library
+            Some(boxsize) => {
+                let mut dist = T::zero();
+                for idx in 0..D {
+                    let image = if *q[idx] < boxsize[idx] / T::from(2.0).unwrap() {
+                        *q[idx] + boxsize[idx]
+                    } else {
+                        *q[idx] - boxsize[idx]
+                    };
+                    dist += (*q[idx] - *d[idx])
+                        .powi(2)
+                        .min((image - *d[idx]).powi(2));
+                }
+                dist
+            }
+            None => squared_euclidean::<T, D>(q, d),
+        };
+        if dist <= radius * radius {
+            all.push((
+                dist,
+                i,
+                #[cfg(not(feature = "no-position"))]
+                d,
+            ))
+        }
+    }
+
+    all.sort_by(|p1, p2| p1.0.partial_cmp(&p2.0).unwrap().then(p1.1.cmp(&p2.1)));
+    #[cfg(feature = "sqrt-dist2")]
+    all.iter_mut().for_each(|p| {
+        p.0 = p.0.sqrt();
+    });
+
+    #[cfg(feature = "no-position")]
+    return all.into_iter().unzip();
+
+    #[cfg(not(feature = "no-position"))]
+    {
+        let mut result = (vec![], vec![], vec![]);
+        for a in all {
+            result.0.push(a.0);
+            result.1.push(a.1);
+            result.2.push(*a.2);
+        }
+        result
+    }
+}
@@ -0,0 +1,119 @@
+use fnntw::{utils::FnntwError, Tree};
+use rand::{rngs::ThreadRng, Rng};
+use std::error::Error;
+
+const NDATA: usize = 1_000;
+const NQUERY: usize = 1_000;
+const BOXSIZE: [f64; 3] = [1.0; 3];
+const D: usize = 3;
+const RADIUS: f64 = 0.2;
+
+#[test]
+fn test_brute_force_count() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random data, query
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+    let query: Vec<[f64; D]> = (0..NQUERY).map(|_| random_point(&mut rng)).collect();
+
+    // Construct tree
+    let tree = Tree::<'_, _, D>::new_parallel(&data, 4, 1)?;
+
+    // Query tree and brute force check results
+    for q in &query {
+        let result = tree.count_within(q, RADIUS)?;
+        let expected = brute_force_count(q, &data, RADIUS, None);
+        assert_eq!(result, expected);
+    }
+
+    // Parallel counts should match
+    #[cfg(feature = "parallel")]
+    for (q, count) in query.iter().zip(tree.count_within_parallel(&query, RADIUS)?) {
+        assert_eq!(count, tree.count_within(q, RADIUS)?);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_brute_force_periodic_count() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random data, query
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+    let query: Vec<[f64; D]> = (0..NQUERY).map(|_| random_point(&mut rng)).collect();
+
+    // Construct tree
+    let tree = Tree::<'_, _, D>::new_parallel(&data, 4, 1)?.with_boxsize(&BOXSIZE)?;
+
+    // Query tree and brute force check results
+    for q in &query {
+        let result = tree.count_within(q, RADIUS)?;
+        let expected = brute_force_count(q, &data, RADIUS, Some(&BOXSIZE));
+        assert_eq!(result, expected);
+    }
+
+    // Parallel counts should match
+    #[cfg(feature = "parallel")]
+    for (q, count) in query.iter().zip(tree.count_within_parallel(&query, RADIUS)?) {
+        assert_eq!(count, tree.count_within(q, RADIUS)?);
+    }
+
+    // Radius larger than half the boxsize is not supported
+    assert!(tree.count_within(&query[0], 0.6).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_periodic_count_half_boxsize() -> Result<(), Box<dyn Error>> {
+    // A point at exactly half the boxsize from the query is within the radius of both the real
+    // query and its shifted image
+    let boxsize = [1.0; 2];
+    let data = [[0.75, 0.5], [0.1, 0.1]];
+    let query = [0.25, 0.5];
+    let tree = Tree::<'_, _, 2>::new(&data, 4)?.with_boxsize(&boxsize)?;
+
+    // So a radius of half the boxsize is not supported
+    assert!(matches!(
+        tree.count_within(&query, 0.5),
+        Err(FnntwError::LargeRadiusPeriodicQuery)
+    ));
+
+    // Just below it, every point is counted once
+    let radius = 0.5 - 1e-12;
+    assert_eq!(tree.count_within(&query, radius)?, 1);
+    assert_eq!(
+        tree.count_within(&query, radius)?,
+        brute_force_count(&query, &data, radius, Some(&boxsize))
+    );
+
+    Ok(())
+}
+
+fn random_point<const D: usize>(rng: &mut ThreadRng) -> [f64; D] {
+    [(); D].map(|_| rng.gen())
+}
+
+fn brute_force_count<const D: usize>(
+    q: &[f64; D],
+    data: &[[f64; D]],
+    radius: f64,
+    boxsize: Option<&[f64; D]>,
+) -> u64 {
+    data.iter()
+        .filter(|d| {
+            let mut dist = 0.0;
+            for idx in 0..D {
+                let mut dx = (q[idx] - d[idx]).abs();
+                if let Some(boxsize) = boxsize {
+                    dx = dx.min(boxsize[idx] - dx);
+                }
+                dist += dx * dx;
+            }
+            dist <= radius * radius
+        })
+        .count() as u64
+}
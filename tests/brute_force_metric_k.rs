@@ -0,0 +1,131 @@
+use fnntw::{
+    distance::{chebyshev, chebyshev_periodic, manhattan, manhattan_periodic},
+    metric::{Chebyshev, Manhattan, Metric},
+    Tree,
+};
+use ordered_float::NotNan;
+use rand::{rngs::ThreadRng, Rng};
+use std::error::Error;
+
+const NDATA: usize = 1_000;
+const NQUERY: usize = 1_000;
+const BOXSIZE: [f64; 3] = [1.0; 3];
+const D: usize = 3;
+// Small enough that the kth nearest neighbor is always well within half of the boxsize
+const K: usize = 8;
+const RADIUS: f64 = 0.3;
+
+type Distance = fn(&[NotNan<f64>; D], &[NotNan<f64>; D]) -> f64;
+type PeriodicDistance = fn(&[NotNan<f64>; D], &[NotNan<f64>; D], &[NotNan<f64>; D]) -> f64;
+
+#[test]
+fn test_brute_force_manhattan_k() -> Result<(), Box<dyn Error>> {
+    check_metric(Manhattan, manhattan, manhattan_periodic)
+}
+
+#[test]
+fn test_brute_force_chebyshev_k() -> Result<(), Box<dyn Error>> {
+    check_metric(Chebyshev, chebyshev, chebyshev_periodic)
+}
+
+fn check_metric<M: Metric<f64, D>>(
+    metric: M,
+    distance: Distance,
+    periodic_distance: PeriodicDistance,
+) -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random data, query
+    let mut data = Vec::with_capacity(NDATA);
+    let mut query = Vec::with_capacity(NQUERY);
+    for _ in 0..NDATA {
+        data.push(random_point(&mut rng));
+    }
+    for _ in 0..NQUERY {
+        query.push(random_point(&mut rng));
+    }
+
+    // Construct tree, checking with and without pbcs
+    let tree = Tree::<'_, _, D>::new_parallel(&data, 1, 1)?.with_metric(metric);
+    check_brute_force(&tree, &query, |a, b| distance(a, b))?;
+    let tree = tree.with_boxsize(&BOXSIZE)?;
+    let boxsize: &[NotNan<f64>; D] = unsafe { std::mem::transmute(&BOXSIZE) };
+    check_brute_force(&tree, &query, |a, b| periodic_distance(a, b, boxsize))?;
+
+    Ok(())
+}
+
+fn check_brute_force<M: Metric<f64, D>>(
+    tree: &Tree<'_, f64, D, M>,
+    query: &[[f64; D]],
+    distance: impl Fn(&[NotNan<f64>; D], &[NotNan<f64>; D]) -> f64,
+) -> Result<(), Box<dyn Error>> {
+    for q in query {
+        let expected = brute_force(q, tree.get_data(), &distance);
+
+        // Nearest neighbor
+        let result = tree.query_nearest(q)?;
+        assert_eq!(result.1, expected[0].1);
+        assert!((result.0 - expected[0].0).abs() < 1e-12);
+
+        // k nearest neighbors
+        let result = tree.query_nearest_k(q, K)?;
+        assert_eq!(result.0.len(), K);
+        for ((d, i), (expected_d, expected_i)) in result.0.iter().zip(&result.1).zip(&expected) {
+            assert_eq!(i, expected_i);
+            assert!((d - expected_d).abs() < 1e-12);
+        }
+
+        // Neighbors within a radius
+        let within = expected.iter().take_while(|(d, _)| *d <= RADIUS).count();
+        assert_eq!(tree.count_within(q, RADIUS)?, within as u64);
+    }
+
+    // k nearest neighbors split into the distance along the first axis and the other axes
+    #[cfg(all(feature = "parallel", feature = "no-position"))]
+    {
+        let result = tree.query_nearest_k_parallel_axis(query, K, 0)?;
+        for (q, j) in query.iter().zip((0..).step_by(K)) {
+            let expected = brute_force(q, tree.get_data(), &distance);
+            for n in 0..K {
+                let (ax, nonax) = (result.0[j + n], result.1[j + n]);
+                assert!((tree.metric().accumulate(nonax, ax) - expected[n].0).abs() < 1e-12);
+
+                // The kth nearest neighbor is well within half of the boxsize along every axis
+                #[cfg(not(feature = "no-index"))]
+                {
+                    let dx = (q[0] - tree.get_data()[result.2[j + n] as usize][0]).abs();
+                    assert!((ax - dx.min(BOXSIZE[0] - dx)).abs() < 1e-12);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn random_point<const D: usize>(rng: &mut ThreadRng) -> [f64; D] {
+    [(); D].map(|_| rng.gen())
+}
+
+/// Returns the distance to and index of every point in `data`, sorted by distance.
+fn brute_force(
+    q: &[f64; D],
+    data: &[[f64; D]],
+    distance: impl Fn(&[NotNan<f64>; D], &[NotNan<f64>; D]) -> f64,
+) -> Vec<(f64, u64)> {
+    // No need for nan checks here
+    let q: &[NotNan<f64>; D] = unsafe { std::mem::transmute(q) };
+    let data: &[[NotNan<f64>; D]] = unsafe { std::mem::transmute(data) };
+
+    let mut all: Vec<(f64, u64)> = data
+        .iter()
+        .zip(0..)
+        .map(|(d, i)| (distance(q, d), i))
+        .collect();
+
+    // this is safe so long as [0, 1] randoms are used
+    all.sort_by(|p1, p2| p1.0.partial_cmp(&p2.0).unwrap());
+    all
+}
@@ -0,0 +1,103 @@
+use fnntw::{metric::Minkowski, Tree};
+use rand::{rngs::ThreadRng, Rng};
+use std::error::Error;
+
+const NDATA: usize = 1_000;
+const NQUERY: usize = 500;
+const BOXSIZE: [f64; 3] = [1.0; 3];
+const D: usize = 3;
+const K: usize = 8;
+const RADIUS: f64 = 0.2;
+const PS: [f64; 4] = [1.0, 1.5, 2.0, 3.5];
+
+#[test]
+fn test_brute_force_minkowski() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random data, query
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+    let query: Vec<[f64; D]> = (0..NQUERY).map(|_| random_point(&mut rng)).collect();
+
+    // Check against brute force for every p, with and without pbcs
+    for p in PS {
+        let tree = Tree::<'_, _, D>::new(&data, 8)?.with_metric(Minkowski::new(p)?);
+        check_minkowski(&tree, &query, p, None)?;
+        let tree = tree.with_boxsize(&BOXSIZE)?;
+        check_minkowski(&tree, &query, p, Some(&BOXSIZE))?;
+    }
+
+    // Invalid p
+    assert!(Minkowski::new(0.5).is_err());
+    assert!(Minkowski::new(f64::INFINITY).is_err());
+    assert!(Minkowski::new(f64::NAN).is_err());
+
+    Ok(())
+}
+
+fn check_minkowski(
+    tree: &Tree<'_, f64, D, Minkowski<f64>>,
+    query: &[[f64; D]],
+    p: f64,
+    boxsize: Option<&[f64; D]>,
+) -> Result<(), Box<dyn Error>> {
+    let data = tree.get_data();
+
+    for q in query {
+        let mut expected: Vec<(f64, u64)> = data
+            .iter()
+            .zip(0..)
+            .map(|(d, index)| (minkowski(q, d, p, boxsize), index))
+            .collect();
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        // k nearest neighbors
+        let result = tree.query_nearest_k(q, K)?;
+        for ((distance, index), (expected_distance, expected_index)) in
+            result.0.iter().zip(&result.1).zip(&expected)
+        {
+            assert_eq!(index, expected_index);
+            assert!((distance - processed(*expected_distance, p)).abs() < 1e-12);
+        }
+
+        // Neighbors within a radius
+        let within: Vec<u64> = expected
+            .iter()
+            .take_while(|(distance, _)| *distance <= RADIUS)
+            .map(|(_, index)| *index)
+            .collect();
+        let result = tree.query_ball_point(q, RADIUS)?;
+        assert_eq!(result.1, within);
+        assert_eq!(tree.count_within(q, RADIUS)?, within.len() as u64);
+    }
+
+    Ok(())
+}
+
+/// The distance returned by queries, which is the `p`th power of the distance unless
+/// `sqrt-dist2` is enabled.
+fn processed(distance: f64, p: f64) -> f64 {
+    #[cfg(feature = "sqrt-dist2")]
+    let _ = p;
+    #[cfg(feature = "sqrt-dist2")]
+    return distance;
+    #[cfg(not(feature = "sqrt-dist2"))]
+    return distance.powf(p);
+}
+
+fn minkowski(a: &[f64; D], b: &[f64; D], p: f64, boxsize: Option<&[f64; D]>) -> f64 {
+    (0..D)
+        .map(|i| {
+            let dx = (a[i] - b[i]).abs();
+            match boxsize {
+                Some(boxsize) => dx.min(boxsize[i] - dx).powf(p),
+                None => dx.powf(p),
+            }
+        })
+        .sum::<f64>()
+        .powf(p.recip())
+}
+
+fn random_point(rng: &mut ThreadRng) -> [f64; D] {
+    [(); D].map(|_| rng.gen())
+}
@@ -0,0 +1,143 @@
+use fnntw::Tree;
+use rand::{rngs::ThreadRng, Rng};
+use std::error::Error;
+
+const NDATA: usize = 1_000;
+const NRANDOM: usize = 2_000;
+const BOXSIZE: [f64; 3] = [1.0; 3];
+const D: usize = 3;
+const BIN_EDGES: [f64; 6] = [0.0, 0.05, 0.1, 0.2, 0.4, 0.6];
+
+#[test]
+fn test_brute_force_pair_count() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random data, randoms, weights
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+    let randoms: Vec<[f64; D]> = (0..NRANDOM).map(|_| random_point(&mut rng)).collect();
+    let data_weights: Vec<f64> = (0..NDATA).map(|_| rng.gen()).collect();
+    let random_weights: Vec<f64> = (0..NRANDOM).map(|_| rng.gen()).collect();
+
+    // Construct trees
+    let data_tree = Tree::<'_, _, D>::new(&data, 8)?;
+    let random_tree = Tree::<'_, _, D>::new(&randoms, 8)?;
+
+    // Count pairs and brute force check results
+    let dd = data_tree.count_pairs(&data_tree, &BIN_EDGES)?;
+    let dr = data_tree.count_pairs(&random_tree, &BIN_EDGES)?;
+    assert_eq!(dd, brute_force_pair_count(&data, &data, None));
+    assert_eq!(dr, brute_force_pair_count(&data, &randoms, None));
+
+    let weighted_dr =
+        data_tree.count_pairs_weighted(&random_tree, &BIN_EDGES, &data_weights, &random_weights)?;
+    let expected = brute_force_weighted(
+        &data,
+        &randoms,
+        Some(&data_weights),
+        Some(&random_weights),
+        None,
+    );
+    assert_close(&weighted_dr, &expected);
+
+    // Invalid bin edges and weights
+    assert!(data_tree.count_pairs(&random_tree, &[0.1]).is_err());
+    assert!(data_tree.count_pairs(&random_tree, &[0.2, 0.1]).is_err());
+    assert!(data_tree
+        .count_pairs_weighted(&random_tree, &BIN_EDGES, &data_weights, &data_weights)
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_brute_force_periodic_pair_count() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random data, randoms, weights
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+    let randoms: Vec<[f64; D]> = (0..NRANDOM).map(|_| random_point(&mut rng)).collect();
+    let data_weights: Vec<f64> = (0..NDATA).map(|_| rng.gen()).collect();
+    let random_weights: Vec<f64> = (0..NRANDOM).map(|_| rng.gen()).collect();
+
+    // Construct trees
+    let data_tree = Tree::<'_, _, D>::new(&data, 8)?.with_boxsize(&BOXSIZE)?;
+    let random_tree = Tree::<'_, _, D>::new(&randoms, 8)?.with_boxsize(&BOXSIZE)?;
+
+    // Count pairs and brute force check results
+    let dd = data_tree.count_pairs(&data_tree, &BIN_EDGES)?;
+    let dr = data_tree.count_pairs(&random_tree, &BIN_EDGES)?;
+    assert_eq!(dd, brute_force_pair_count(&data, &data, Some(&BOXSIZE)));
+    assert_eq!(dr, brute_force_pair_count(&data, &randoms, Some(&BOXSIZE)));
+
+    let weighted_dr =
+        data_tree.count_pairs_weighted(&random_tree, &BIN_EDGES, &data_weights, &random_weights)?;
+    let expected = brute_force_weighted(
+        &data,
+        &randoms,
+        Some(&data_weights),
+        Some(&random_weights),
+        Some(&BOXSIZE),
+    );
+    assert_close(&weighted_dr, &expected);
+
+    // Trees must share a boxsize
+    let nonperiodic_tree = Tree::<'_, _, D>::new(&randoms, 8)?;
+    assert!(data_tree
+        .count_pairs(&nonperiodic_tree, &BIN_EDGES)
+        .is_err());
+
+    Ok(())
+}
+
+fn random_point<const D: usize>(rng: &mut ThreadRng) -> [f64; D] {
+    [(); D].map(|_| rng.gen())
+}
+
+fn assert_close(result: &[f64], expected: &[f64]) {
+    assert_eq!(result.len(), expected.len());
+    for (r, e) in result.iter().zip(expected) {
+        assert!((r - e).abs() <= 1e-9 * e.abs().max(1.0), "{r} != {e}");
+    }
+}
+
+fn brute_force_pair_count<const D: usize>(
+    a: &[[f64; D]],
+    b: &[[f64; D]],
+    boxsize: Option<&[f64; D]>,
+) -> Vec<u64> {
+    brute_force_weighted(a, b, None, None, boxsize)
+        .into_iter()
+        .map(|count| count as u64)
+        .collect()
+}
+
+fn brute_force_weighted<const D: usize>(
+    a: &[[f64; D]],
+    b: &[[f64; D]],
+    a_weights: Option<&[f64]>,
+    b_weights: Option<&[f64]>,
+    boxsize: Option<&[f64; D]>,
+) -> Vec<f64> {
+    let mut counts = vec![0.0; BIN_EDGES.len() - 1];
+    for (i, p) in a.iter().enumerate() {
+        for (j, q) in b.iter().enumerate() {
+            let mut dist2 = 0.0;
+            for idx in 0..D {
+                let mut dx = (p[idx] - q[idx]).abs();
+                if let Some(boxsize) = boxsize {
+                    dx = dx.min(boxsize[idx] - dx);
+                }
+                dist2 += dx * dx;
+            }
+            for bin in 0..BIN_EDGES.len() - 1 {
+                if BIN_EDGES[bin].powi(2) <= dist2 && dist2 < BIN_EDGES[bin + 1].powi(2) {
+                    counts[bin] +=
+                        a_weights.map_or(1.0, |w| w[i]) * b_weights.map_or(1.0, |w| w[j]);
+                }
+            }
+        }
+    }
+    counts
+}
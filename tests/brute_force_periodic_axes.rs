@@ -0,0 +1,128 @@
+use fnntw::{metric::Manhattan, Tree};
+use rand::{rngs::ThreadRng, Rng};
+use std::error::Error;
+
+const NDATA: usize = 2_000;
+const NQUERY: usize = 500;
+const D: usize = 3;
+const K: usize = 8;
+const RADIUS: f64 = 0.2;
+// Periodic in x and y, open in z
+const BOXSIZE: [Option<f64>; D] = [Some(1.0), Some(1.0), None];
+
+#[test]
+fn test_brute_force_periodic_axes() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random data, query in a slab, which may extend to negative z
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+    let query: Vec<[f64; D]> = (0..NQUERY).map(|_| random_point(&mut rng)).collect();
+
+    // Check the euclidean and a non-default metric against brute force
+    let tree = Tree::<'_, _, D>::new(&data, 8)?.with_periodic_axes(&BOXSIZE)?;
+    check_brute_force(&tree, &query, |dx: [f64; D]| {
+        dx.iter().map(|dx| dx * dx).sum::<f64>().sqrt()
+    })?;
+    let tree = tree.with_metric(Manhattan);
+    check_brute_force(&tree, &query, |dx: [f64; D]| dx.iter().sum::<f64>())?;
+
+    Ok(())
+}
+
+#[test]
+fn test_periodic_axes_boxsize() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+
+    // Without any periodic axis, queries are nonperiodic
+    let tree = Tree::<'_, _, D>::new(&data, 8)?;
+    let open = Tree::<'_, _, D>::new(&data, 8)?.with_periodic_axes(&[None; D])?;
+    for q in &data[..100] {
+        assert_eq!(tree.query_nearest_k(q, K)?, open.query_nearest_k(q, K)?);
+    }
+
+    // The data must fit in the box along the periodic axes only
+    let tree = Tree::<'_, _, D>::new(&data, 8)?;
+    assert!(tree.with_periodic_axes(&[None, None, Some(1.0)]).is_err());
+    let tree = Tree::<'_, _, D>::new(&data, 8)?;
+    assert!(tree.with_periodic_axes(&[Some(0.5), None, None]).is_err());
+    let tree = Tree::<'_, _, D>::new(&data, 8)?;
+    assert!(tree
+        .with_periodic_axes(&[Some(f64::NAN), None, None])
+        .is_err());
+
+    Ok(())
+}
+
+fn check_brute_force<M: fnntw::metric::Metric<f64, D>>(
+    tree: &Tree<'_, f64, D, M>,
+    query: &[[f64; D]],
+    distance: impl Fn([f64; D]) -> f64,
+) -> Result<(), Box<dyn Error>> {
+    let data = tree.get_data();
+
+    for q in query {
+        let mut expected: Vec<(f64, u64)> = data
+            .iter()
+            .zip(0..)
+            .map(|(d, index)| (distance(separation(q, d)), index))
+            .collect();
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        // Nearest neighbor
+        let result = tree.query_nearest(q)?;
+        assert_eq!(result.1, expected[0].1);
+
+        // k nearest neighbors
+        let result = tree.query_nearest_k(q, K)?;
+        assert_eq!(result.1.len(), K);
+        for (index, (_, expected_index)) in result.1.iter().zip(&expected) {
+            assert_eq!(index, expected_index);
+        }
+
+        // Neighbors within a radius
+        let within: Vec<u64> = expected
+            .iter()
+            .take_while(|(distance, _)| *distance <= RADIUS)
+            .map(|(_, index)| *index)
+            .collect();
+        let result = tree.query_ball_point(q, RADIUS)?;
+        assert_eq!(result.1, within);
+        assert_eq!(tree.count_within(q, RADIUS)?, within.len() as u64);
+
+        // Neighbors within a range, which wraps around the periodic axes only
+        let (lower, upper) = (q.map(|x| x - RADIUS), q.map(|x| x + RADIUS));
+        let in_range: Vec<u64> = data
+            .iter()
+            .zip(0..)
+            .filter(|(d, _)| {
+                (0..D).all(|i| match BOXSIZE[i] {
+                    Some(side) => (d[i] - lower[i]).rem_euclid(side) <= 2.0 * RADIUS,
+                    None => lower[i] <= d[i] && d[i] <= upper[i],
+                })
+            })
+            .map(|(_, index)| index)
+            .collect();
+        assert_eq!(tree.query_range(&lower, &upper)?, in_range);
+    }
+
+    Ok(())
+}
+
+/// Minimum image separation along the periodic axes, ordinary separation along the others
+fn separation(a: &[f64; D], b: &[f64; D]) -> [f64; D] {
+    let mut dx = [0.0; D];
+    for i in 0..D {
+        dx[i] = (a[i] - b[i]).abs();
+        if let Some(side) = BOXSIZE[i] {
+            dx[i] = dx[i].min(side - dx[i]);
+        }
+    }
+    dx
+}
+
+fn random_point(rng: &mut ThreadRng) -> [f64; D] {
+    [rng.gen(), rng.gen(), rng.gen_range(-1.0..1.0)]
+}
@@ -0,0 +1,177 @@
+use fnntw::{utils::FnntwError, Tree};
+use rand::{rngs::ThreadRng, Rng};
+use std::error::Error;
+
+const NDATA: usize = 2_000;
+const NQUERY: usize = 500;
+const D: usize = 3;
+const K: usize = 8;
+const RADIUS: f64 = 0.2;
+
+#[test]
+fn test_brute_force_periodic_box() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // A box centered on the origin, and one away from it
+    for (lower, upper) in [
+        ([-0.5; D], [0.5; D]),
+        ([2.0, -3.0, 10.0], [3.0, -2.5, 11.5]),
+    ] {
+        // Generate random data, query within the box
+        let data: Vec<[f64; D]> = (0..NDATA)
+            .map(|_| random_point(&mut rng, &lower, &upper))
+            .collect();
+        let query: Vec<[f64; D]> = (0..NQUERY)
+            .map(|_| random_point(&mut rng, &lower, &upper))
+            .collect();
+
+        // Periodic along every axis
+        let bounds = [0, 1, 2].map(|i| Some((lower[i], upper[i])));
+        let tree = Tree::<'_, _, D>::new(&data, 8)?.with_periodic_box(&lower, &upper)?;
+        check_brute_force(&tree, &query, &bounds)?;
+
+        // Open along the last axis
+        let bounds = [bounds[0], bounds[1], None];
+        let tree = Tree::<'_, _, D>::new(&data, 8)?.with_periodic_bounds(&bounds)?;
+        check_brute_force(&tree, &query, &bounds)?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_periodic_box_bounds() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+    let data: Vec<[f64; D]> = (0..NDATA)
+        .map(|_| random_point(&mut rng, &[-0.5; D], &[0.5; D]))
+        .collect();
+
+    // The data must lie within the box
+    let tree = Tree::<'_, _, D>::new(&data, 8)?;
+    assert!(matches!(
+        tree.with_boxsize(&[1.0; D]),
+        Err(FnntwError::NegativeDataPeriodicQuery)
+    ));
+    let tree = Tree::<'_, _, D>::new(&data, 8)?;
+    assert!(matches!(
+        tree.with_periodic_box(&[-0.5; D], &[0.25; D]),
+        Err(FnntwError::SmallBoxsize)
+    ));
+    let tree = Tree::<'_, _, D>::new(&data, 8)?;
+    assert!(matches!(
+        tree.with_periodic_box(&[-0.5; D], &[f64::INFINITY; D]),
+        Err(FnntwError::InvalidBoxsize)
+    ));
+    let tree = Tree::<'_, _, D>::new(&data, 8)?;
+    assert!(matches!(
+        tree.with_periodic_box(&[0.0; D], &[0.0; D]),
+        Err(FnntwError::InvalidBoxsize)
+    ));
+    let tree = Tree::<'_, _, D>::new(&data, 8)?;
+    assert!(matches!(
+        tree.with_periodic_box(&[-f64::MAX; D], &[f64::MAX; D]),
+        Err(FnntwError::InvalidBoxsize)
+    ));
+
+    // Shifting the data and the box together does not change any result
+    let shifted: Vec<[f64; D]> = data.iter().map(|p| p.map(|x| x + 0.5)).collect();
+    let centered = Tree::<'_, _, D>::new(&data, 8)?.with_periodic_box(&[-0.5; D], &[0.5; D])?;
+    let shifted = Tree::<'_, _, D>::new(&shifted, 8)?.with_boxsize(&[1.0; D])?;
+    for q in &data[..NQUERY] {
+        let (a, b) = (
+            centered.query_nearest_k(q, K)?,
+            shifted.query_nearest_k(&q.map(|x| x + 0.5), K)?,
+        );
+        assert_eq!(a.1, b.1);
+    }
+
+    Ok(())
+}
+
+fn check_brute_force(
+    tree: &Tree<'_, f64, D>,
+    query: &[[f64; D]],
+    bounds: &[Option<(f64, f64)>; D],
+) -> Result<(), Box<dyn Error>> {
+    let data = tree.get_data();
+
+    for q in query {
+        let mut expected: Vec<(f64, u64)> = data
+            .iter()
+            .zip(0..)
+            .map(|(d, index)| (distance(q, d, bounds), index))
+            .collect();
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        // Nearest neighbor
+        let result = tree.query_nearest(q)?;
+        assert_eq!(result.1, expected[0].1);
+
+        // k nearest neighbors
+        let result = tree.query_nearest_k(q, K)?;
+        for ((distance, index), (expected_distance, expected_index)) in
+            result.0.iter().zip(&result.1).zip(&expected)
+        {
+            assert_eq!(index, expected_index);
+            assert!((distance - processed(*expected_distance)).abs() < 1e-12);
+        }
+
+        // Neighbors within a radius
+        let within: Vec<u64> = expected
+            .iter()
+            .take_while(|(distance, _)| *distance <= RADIUS)
+            .map(|(_, index)| *index)
+            .collect();
+        let result = tree.query_ball_point(q, RADIUS)?;
+        assert_eq!(result.1, within);
+        assert_eq!(tree.count_within(q, RADIUS)?, within.len() as u64);
+
+        // Neighbors within a range, which wraps around the periodic axes
+        let (lower, upper) = (q.map(|x| x - RADIUS), q.map(|x| x + RADIUS));
+        let in_range: Vec<u64> = data
+            .iter()
+            .zip(0..)
+            .filter(|(d, _)| {
+                (0..D).all(|i| match bounds[i] {
+                    Some((box_lower, box_upper)) => {
+                        (d[i] - lower[i]).rem_euclid(box_upper - box_lower) <= 2.0 * RADIUS
+                    }
+                    None => lower[i] <= d[i] && d[i] <= upper[i],
+                })
+            })
+            .map(|(_, index)| index)
+            .collect();
+        assert_eq!(tree.query_range(&lower, &upper)?, in_range);
+    }
+
+    Ok(())
+}
+
+/// The distance returned by queries, which is the squared distance unless `sqrt-dist2` is
+/// enabled.
+fn processed(distance: f64) -> f64 {
+    #[cfg(feature = "sqrt-dist2")]
+    return distance;
+    #[cfg(not(feature = "sqrt-dist2"))]
+    return distance.powi(2);
+}
+
+/// Minimum image distance along the periodic axes, ordinary separation along the others
+fn distance(a: &[f64; D], b: &[f64; D], bounds: &[Option<(f64, f64)>; D]) -> f64 {
+    (0..D)
+        .map(|i| {
+            let dx = (a[i] - b[i]).abs();
+            match bounds[i] {
+                Some((lower, upper)) => dx.min(upper - lower - dx).powi(2),
+                None => dx.powi(2),
+            }
+        })
+        .sum::<f64>()
+        .sqrt()
+}
+
+fn random_point(rng: &mut ThreadRng, lower: &[f64; D], upper: &[f64; D]) -> [f64; D] {
+    [0, 1, 2].map(|i| rng.gen_range(lower[i]..upper[i]))
+}
@@ -0,0 +1,73 @@
+use fnntw::Tree;
+use rand::{rngs::ThreadRng, Rng};
+use std::error::Error;
+
+const NDATA: usize = 2_000;
+const NRANGES: usize = 500;
+const BOXSIZE: [f64; 3] = [1.0; 3];
+const D: usize = 3;
+
+#[test]
+fn test_brute_force_range() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random data, and ranges that may cross (or lie beyond) the edges of the box
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| [(); D].map(|_| rng.gen())).collect();
+    let mut ranges: Vec<([f64; D], [f64; D])> =
+        (0..NRANGES).map(|_| random_range(&mut rng)).collect();
+    ranges.push(([-0.1, 0.2, 2.3], [1.5, 0.4, 2.5]));
+    ranges.push(([0.0; D], [1.0; D]));
+
+    // Check against brute force, with and without pbcs
+    for leafsize in [1, 16] {
+        let tree = Tree::<'_, _, D>::new(&data, leafsize)?;
+        for (lower, upper) in &ranges {
+            let result = tree.query_range(lower, upper)?;
+            assert_eq!(result, brute_force(&data, lower, upper, None));
+        }
+
+        let tree = tree.with_boxsize(&BOXSIZE)?;
+        for (lower, upper) in &ranges {
+            let result = tree.query_range(lower, upper)?;
+            assert_eq!(result, brute_force(&data, lower, upper, Some(&BOXSIZE)));
+        }
+    }
+
+    // Invalid ranges
+    let tree = Tree::<'_, _, D>::new(&data, 16)?;
+    assert!(tree.query_range(&[0.5; D], &[0.4; D]).is_err());
+    assert!(tree.query_range(&[f64::NAN; D], &[0.4; D]).is_err());
+
+    Ok(())
+}
+
+fn random_range(rng: &mut ThreadRng) -> ([f64; D], [f64; D]) {
+    let lower: [f64; D] = [(); D].map(|_| rng.gen_range(-0.5..1.0));
+    let mut upper = lower;
+    for idx in 0..D {
+        upper[idx] += rng.gen_range(0.0..0.6);
+    }
+    (lower, upper)
+}
+
+fn brute_force(
+    data: &[[f64; D]],
+    lower: &[f64; D],
+    upper: &[f64; D],
+    boxsize: Option<&[f64; D]>,
+) -> Vec<u64> {
+    data.iter()
+        .zip(0..)
+        .filter(|(d, _)| {
+            (0..D).all(|idx| match boxsize {
+                Some(boxsize) => {
+                    let width = upper[idx] - lower[idx];
+                    width >= boxsize[idx] || (d[idx] - lower[idx]).rem_euclid(boxsize[idx]) <= width
+                }
+                None => lower[idx] <= d[idx] && d[idx] <= upper[idx],
+            })
+        })
+        .map(|(_, index)| index)
+        .collect()
+}
@@ -0,0 +1,210 @@
+use fnntw::{
+    triclinic::{TriclinicCell, TriclinicTree},
+    utils::FnntwError,
+};
+use rand::{rngs::ThreadRng, Rng};
+use std::error::Error;
+
+const NDATA: usize = 2_000;
+const NQUERY: usize = 200;
+const D: usize = 3;
+const K: usize = 8;
+const RADIUS: f64 = 0.15;
+
+/// A sheared cell, one lattice vector per row
+const VECTORS: [[f64; D]; D] = [[1.0, 0.0, 0.0], [0.4, 1.0, 0.0], [0.3, -0.2, 1.0]];
+
+#[test]
+fn test_brute_force_triclinic() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+    let cell = TriclinicCell::new(&VECTORS)?;
+
+    // Generate random data, query within the cell
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng, &cell)).collect();
+    let query: Vec<[f64; D]> = (0..NQUERY).map(|_| random_point(&mut rng, &cell)).collect();
+
+    let tree = TriclinicTree::new(&data, &VECTORS, 8)?;
+    for q in &query {
+        let mut expected: Vec<(f64, u64)> = data
+            .iter()
+            .zip(0..)
+            .map(|(d, index)| (distance(q, d), index))
+            .collect();
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        // Nearest neighbor
+        let result = tree.query_nearest(q)?;
+        assert_eq!(result.1, expected[0].1);
+
+        // k nearest neighbors
+        let result = tree.query_nearest_k(q, K)?;
+        for ((distance, index), (expected_distance, expected_index)) in
+            result.0.iter().zip(&result.1).zip(&expected)
+        {
+            assert_eq!(index, expected_index);
+            assert!((distance - processed(*expected_distance)).abs() < 1e-12);
+        }
+
+        // Minimum image separations of the k nearest neighbors
+        let (distances, indices, separations) = tree.query_nearest_k_separation(q, K)?;
+        assert_eq!(indices, result.1);
+        assert_eq!(distances, result.0);
+        for (index, separation) in indices.iter().zip(&separations) {
+            let expected = separation_brute_force(q, &data[*index as usize]);
+            assert!((0..D).all(|i| (separation[i] - expected[i]).abs() < 1e-12));
+        }
+
+        // Neighbors within a radius
+        let within: Vec<u64> = expected
+            .iter()
+            .take_while(|(distance, _)| *distance <= RADIUS)
+            .map(|(_, index)| *index)
+            .collect();
+        let result = tree.query_ball_point(q, RADIUS)?;
+        assert_eq!(result.1, within);
+        assert_eq!(tree.count_within(q, RADIUS)?, within.len() as u64);
+
+        // Queries outside of the cell are wrapped into it
+        let shifted = cell.cartesian(&cell.fractional(q).map(|f| f - 2.0));
+        let expected: Vec<u64> = expected[..K].iter().map(|(_, index)| *index).collect();
+        assert_eq!(tree.query_nearest_k(&shifted, K)?.1, expected);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_triclinic_cell() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+    let cell = TriclinicCell::new(&VECTORS)?;
+
+    // Fractional and cartesian coordinates are inverses
+    let point = random_point(&mut rng, &cell);
+    let roundtrip = cell.cartesian(&cell.fractional(&point));
+    assert!((0..D).all(|i| (point[i] - roundtrip[i]).abs() < 1e-12));
+
+    // The widths of a rectangular cell are its sides
+    let rectangular = TriclinicCell::new(&[[1.0, 0.0, 0.0], [0.0, 2.0, 0.0], [0.0, 0.0, 3.0]])?;
+    assert_eq!(rectangular.widths(), &[1.0, 2.0, 3.0]);
+
+    // Singular and nonfinite lattices are rejected
+    assert!(matches!(
+        TriclinicCell::new(&[[1.0, 0.0, 0.0], [2.0, 0.0, 0.0], [0.0, 0.0, 1.0]]),
+        Err(FnntwError::InvalidLattice)
+    ));
+    assert!(matches!(
+        TriclinicCell::new(&[[f64::NAN, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]),
+        Err(FnntwError::InvalidLattice)
+    ));
+
+    // The data must lie within the cell
+    let data = [cell.cartesian(&[0.5, 0.5, 1.5])];
+    assert!(matches!(
+        TriclinicTree::new(&data, &VECTORS, 8),
+        Err(FnntwError::SmallBoxsize)
+    ));
+    let data = [cell.cartesian(&[0.5, -0.5, 0.5])];
+    assert!(matches!(
+        TriclinicTree::new(&data, &VECTORS, 8),
+        Err(FnntwError::NegativeDataPeriodicQuery)
+    ));
+
+    // k must be nonzero
+    let data = [cell.cartesian(&[0.5, 0.5, 0.5])];
+    let tree = TriclinicTree::new(&data, &VECTORS, 8)?;
+    assert!(matches!(
+        tree.query_nearest_k(&data[0], 0),
+        Err(FnntwError::InvalidK)
+    ));
+    assert!(matches!(
+        tree.query_nearest_k_separation(&data[0], 0),
+        Err(FnntwError::InvalidK)
+    ));
+
+    // The radius must be smaller than half of the smallest width
+    assert!(matches!(
+        tree.query_ball_point(&data[0], 0.5),
+        Err(FnntwError::LargeRadiusPeriodicQuery)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_triclinic_half_width() -> Result<(), Box<dyn Error>> {
+    // At exactly half of the width, the point at [0.75, 0.5] is at the radius from both the
+    // query and its image at [1.25, 0.5], so it would be counted twice
+    let vectors = [[1.0, 0.0], [0.0, 1.0]];
+    let data = [[0.75, 0.5], [0.1, 0.1]];
+    let query = [0.25, 0.5];
+    let tree = TriclinicTree::new(&data, &vectors, 8)?;
+    assert!(matches!(
+        tree.query_ball_point(&query, 0.5),
+        Err(FnntwError::LargeRadiusPeriodicQuery)
+    ));
+    assert!(matches!(
+        tree.count_within(&query, 0.5),
+        Err(FnntwError::LargeRadiusPeriodicQuery)
+    ));
+
+    // Just below half of the width, only the point at [0.1, 0.1] is found
+    let radius = 0.5 - 1e-12;
+    assert_eq!(tree.query_ball_point(&query, radius)?.1, vec![1]);
+    assert_eq!(tree.count_within(&query, radius)?, 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_triclinic_corner_image() -> Result<(), Box<dyn Error>> {
+    // The nearest neighbor of a query near the lower corner is the image of a point near the
+    // upper corner, which is only found by shifting the query along every lattice vector
+    let vectors = [[1.0, 0.0], [0.0, 1.0]];
+    let data = [[0.98, 0.98], [0.1, 0.1], [0.5, 0.5]];
+    let tree = TriclinicTree::new(&data, &vectors, 1)?;
+    assert_eq!(tree.query_nearest(&[0.02, 0.02])?.1, 0);
+
+    Ok(())
+}
+
+/// The distance returned by queries, which is the squared distance unless `sqrt-dist2` is
+/// enabled.
+fn processed(distance: f64) -> f64 {
+    #[cfg(feature = "sqrt-dist2")]
+    return distance;
+    #[cfg(not(feature = "sqrt-dist2"))]
+    return distance.powi(2);
+}
+
+/// Minimum image distance over all images shifted by up to two lattice vectors along every axis
+fn distance(a: &[f64; D], b: &[f64; D]) -> f64 {
+    let separation = separation_brute_force(a, b);
+    separation.iter().map(|x| x.powi(2)).sum::<f64>().sqrt()
+}
+
+/// Shortest separation from `a` to the images of `b` shifted by up to two lattice vectors along
+/// every axis
+fn separation_brute_force(a: &[f64; D], b: &[f64; D]) -> [f64; D] {
+    let mut min = (f64::INFINITY, [0.0; D]);
+    for i in -2..=2 {
+        for j in -2..=2 {
+            for k in -2..=2 {
+                let shift = [i, j, k].map(|n| n as f64);
+                let separation: [f64; D] = std::array::from_fn(|axis| {
+                    b[axis] + (0..D).map(|n| shift[n] * VECTORS[n][axis]).sum::<f64>() - a[axis]
+                });
+                let dist2 = separation.iter().map(|x| x.powi(2)).sum::<f64>();
+                if dist2 < min.0 {
+                    min = (dist2, separation);
+                }
+            }
+        }
+    }
+    min.1
+}
+
+fn random_point(rng: &mut ThreadRng, cell: &TriclinicCell<f64, D>) -> [f64; D] {
+    cell.cartesian(&[(); D].map(|_| rng.gen_range(0.0..1.0)))
+}
@@ -0,0 +1,123 @@
+use fnntw::{metric::WeightedSquaredEuclidean, Tree};
+use rand::{rngs::ThreadRng, Rng};
+use std::error::Error;
+
+const NDATA: usize = 1_000;
+const NQUERY: usize = 500;
+const BOXSIZE: [f64; 3] = [1.0; 3];
+const D: usize = 3;
+const K: usize = 8;
+const RADIUS: f64 = 0.2;
+const WEIGHTS: [[f64; D]; 3] = [[1.0, 1.0, 0.25], [4.0, 1.0, 2.5], [0.5, 3.0, 1.0]];
+
+#[test]
+fn test_brute_force_weighted() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random data, query
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+    let query: Vec<[f64; D]> = (0..NQUERY).map(|_| random_point(&mut rng)).collect();
+
+    // Check against brute force for every set of weights, with and without pbcs
+    for weights in WEIGHTS {
+        let metric = WeightedSquaredEuclidean::new(weights)?;
+        let tree = Tree::<'_, _, D>::new(&data, 8)?.with_metric(metric);
+        check_weighted(&tree, &query, &weights, None)?;
+        let tree = tree.with_boxsize(&BOXSIZE)?;
+        check_weighted(&tree, &query, &weights, Some(&BOXSIZE))?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_weighted_periodic_radius() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+
+    // Half of the boxsize along the last axis only spans a distance of 0.25
+    let metric = WeightedSquaredEuclidean::new([1.0, 1.0, 0.25])?;
+    let tree = Tree::<'_, _, D>::new(&data, 8)?
+        .with_metric(metric)
+        .with_boxsize(&BOXSIZE)?;
+    assert!(tree.query_ball_point(&[0.5; D], 0.2).is_ok());
+    assert!(tree.query_ball_point(&[0.5; D], 0.25).is_err());
+    assert!(tree.query_ball_point(&[0.5; D], 0.3).is_err());
+    assert!(tree.count_within(&[0.5; D], 0.3).is_err());
+
+    // Invalid weights
+    assert!(WeightedSquaredEuclidean::new([1.0, 0.0, 1.0]).is_err());
+    assert!(WeightedSquaredEuclidean::new([1.0, -1.0, 1.0]).is_err());
+    assert!(WeightedSquaredEuclidean::new([1.0, f64::INFINITY, 1.0]).is_err());
+    assert!(WeightedSquaredEuclidean::new([1.0, f64::NAN, 1.0]).is_err());
+
+    Ok(())
+}
+
+fn check_weighted(
+    tree: &Tree<'_, f64, D, WeightedSquaredEuclidean<f64, D>>,
+    query: &[[f64; D]],
+    weights: &[f64; D],
+    boxsize: Option<&[f64; D]>,
+) -> Result<(), Box<dyn Error>> {
+    let data = tree.get_data();
+
+    for q in query {
+        let mut expected: Vec<(f64, u64)> = data
+            .iter()
+            .zip(0..)
+            .map(|(d, index)| (weighted(q, d, weights, boxsize), index))
+            .collect();
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        // k nearest neighbors
+        let result = tree.query_nearest_k(q, K)?;
+        for ((distance, index), (expected_distance, expected_index)) in
+            result.0.iter().zip(&result.1).zip(&expected)
+        {
+            assert_eq!(index, expected_index);
+            assert!((distance - processed(*expected_distance)).abs() < 1e-12);
+        }
+
+        // Neighbors within a radius
+        let within: Vec<u64> = expected
+            .iter()
+            .take_while(|(distance, _)| *distance <= RADIUS)
+            .map(|(_, index)| *index)
+            .collect();
+        let result = tree.query_ball_point(q, RADIUS)?;
+        assert_eq!(result.1, within);
+        assert_eq!(tree.count_within(q, RADIUS)?, within.len() as u64);
+    }
+
+    Ok(())
+}
+
+/// The distance returned by queries, which is the squared distance unless `sqrt-dist2` is
+/// enabled.
+fn processed(distance: f64) -> f64 {
+    #[cfg(feature = "sqrt-dist2")]
+    return distance;
+    #[cfg(not(feature = "sqrt-dist2"))]
+    return distance.powi(2);
+}
+
+fn weighted(a: &[f64; D], b: &[f64; D], weights: &[f64; D], boxsize: Option<&[f64; D]>) -> f64 {
+    (0..D)
+        .map(|i| {
+            let dx = (a[i] - b[i]).abs();
+            let dx = match boxsize {
+                Some(boxsize) => dx.min(boxsize[i] - dx),
+                None => dx,
+            };
+            weights[i] * dx * dx
+        })
+        .sum::<f64>()
+        .sqrt()
+}
+
+fn random_point(rng: &mut ThreadRng) -> [f64; D] {
+    [(); D].map(|_| rng.gen())
+}
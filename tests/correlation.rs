@@ -0,0 +1,88 @@
+use fnntw::{correlation::Estimator, utils::FnntwError, Tree};
+use rand::{rngs::ThreadRng, Rng};
+use std::error::Error;
+
+const NDATA: usize = 2_000;
+const BOXSIZE: [f64; 3] = [1.0; 3];
+const D: usize = 3;
+const BIN_EDGES: [f64; 5] = [0.0, 0.05, 0.1, 0.2, 0.3];
+
+#[test]
+fn test_correlation_function_estimators() -> Result<(), Box<dyn Error>> {
+    // A small catalog on a line, whose pair counts are easily found by hand
+    let data: [[f64; 1]; 3] = [[0.0], [0.1], [0.35]];
+    let randoms = [[0.0], [0.05], [0.3], [0.9]];
+    let bin_edges = [0.0, 0.15, 0.4];
+    let data_tree = Tree::<'_, _, 1>::new(&data, 1)?;
+    let random_tree = Tree::<'_, _, 1>::new(&randoms, 1)?;
+
+    // Normalized pair counts in each bin:
+    // DD: separations 0.1 | 0.25, 0.35, i.e. (2 | 4 ordered pairs) / (3 * 2)
+    // DR: separations 0, 0.05, 0.1, 0.05, 0.05 | 0.3, 0.2, 0.35, 0.3, i.e. (5 | 4) / (3 * 4)
+    // RR: separations 0.05 | 0.25, 0.3, i.e. (2 | 4 ordered pairs) / (4 * 3)
+    // so that dd = [1/3, 2/3], dr = [5/12, 1/3] and rr = [1/6, 1/3]
+    for (estimator, expected) in [
+        // dd / rr - 1
+        (Estimator::Natural, [1.0, 1.0]),
+        // dd / dr - 1
+        (Estimator::DavisPeebles, [-0.2, 1.0]),
+        // dd * rr / dr^2 - 1
+        (Estimator::Hamilton, [-0.68, 1.0]),
+        // (dd - 2 dr + rr) / rr
+        (Estimator::LandySzalay, [-2.0, 1.0]),
+    ] {
+        let xi = data_tree.correlation_function(&random_tree, &bin_edges, estimator)?;
+        for (xi, expected) in xi.iter().zip(expected) {
+            assert!(
+                (xi - expected).abs() < 1e-12,
+                "{estimator:?}: {xi} != {expected}"
+            );
+        }
+    }
+
+    // Catalogs with a single point have no pairs
+    let single_tree = Tree::<'_, _, 1>::new(&data[..1], 1)?;
+    assert!(matches!(
+        single_tree.correlation_function(&random_tree, &bin_edges, Estimator::Natural),
+        Err(FnntwError::TooFewPoints)
+    ));
+    assert!(matches!(
+        data_tree.correlation_function(&single_tree, &bin_edges, Estimator::LandySzalay),
+        Err(FnntwError::TooFewPoints)
+    ));
+
+    // Periodic estimator requires a boxsize
+    assert!(matches!(
+        data_tree.correlation_function_periodic(&bin_edges),
+        Err(FnntwError::MissingBoxsize)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_correlation_function_periodic() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate uniform random data, which is uncorrelated
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+
+    // Construct tree
+    let tree = Tree::<'_, _, D>::new(&data, 8)?.with_boxsize(&BOXSIZE)?;
+
+    // Skip the noisy first bin
+    let xi = tree.correlation_function_periodic(&BIN_EDGES[1..])?;
+    for x in xi {
+        assert!(x.abs() < 0.1, "{x}");
+    }
+
+    // Bin edges larger than half the boxsize are not supported
+    assert!(tree.correlation_function_periodic(&[0.1, 0.6]).is_err());
+
+    Ok(())
+}
+
+fn random_point<const D: usize>(rng: &mut ThreadRng) -> [f64; D] {
+    [(); D].map(|_| rng.gen())
+}
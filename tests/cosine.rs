@@ -0,0 +1,107 @@
+use fnntw::{
+    cosine::{normalize, CosineTree},
+    utils::FnntwError,
+};
+use rand::{rngs::ThreadRng, Rng};
+use std::error::Error;
+
+const NDATA: usize = 2_000;
+const NQUERY: usize = 500;
+const D: usize = 8;
+const K: usize = 8;
+
+#[test]
+fn test_brute_force_cosine() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random data, query with a range of norms
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+    let query: Vec<[f64; D]> = (0..NQUERY).map(|_| random_point(&mut rng)).collect();
+
+    let tree = CosineTree::new(&data, 8)?;
+    let par_tree = CosineTree::new_parallel(&data, 8, 1)?;
+    for q in &query {
+        let mut expected: Vec<(f64, u64)> = data
+            .iter()
+            .zip(0..)
+            .map(|(d, index)| (cosine(q, d), index))
+            .collect();
+        // Descending similarity
+        expected.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+
+        // Most similar point
+        let result = tree.query_nearest(q)?;
+        assert_eq!(result.1, expected[0].1);
+        assert!((result.0 - expected[0].0).abs() < 1e-12);
+
+        // k most similar points, with either build
+        let result = tree.query_nearest_k(q, K)?;
+        assert_eq!(result, par_tree.query_nearest_k(q, K)?);
+        assert_eq!(result.0.len(), K);
+        for ((similarity, index), (expected_similarity, expected_index)) in
+            result.0.iter().zip(&result.1).zip(&expected)
+        {
+            assert_eq!(index, expected_index);
+            assert!((similarity - expected_similarity).abs() < 1e-12);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_zero_norm() -> Result<(), Box<dyn Error>> {
+    // Zero norm rows are rejected
+    let data = [[1.0, 0.0], [0.0, 0.0]];
+    assert!(matches!(
+        CosineTree::new(&data, 1),
+        Err(FnntwError::ZeroNormVector)
+    ));
+
+    // As are zero norm queries
+    let data = [[1.0, 0.0], [0.0, 2.0], [-3.0, 0.0]];
+    let tree = CosineTree::new(&data, 1)?;
+    assert!(matches!(
+        tree.query_nearest(&[0.0, 0.0]),
+        Err(FnntwError::ZeroNormVector)
+    ));
+
+    // Only the direction matters
+    assert_eq!(tree.query_nearest(&[0.0, 0.001])?, (1.0, 1));
+    assert_eq!(
+        tree.query_nearest_k(&[-5.0, 0.0], 3)?,
+        (vec![1.0, 0.0, -1.0], vec![2, 1, 0])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_extreme_norms() -> Result<(), Box<dyn Error>> {
+    // Tiny and huge norms neither underflow nor overflow
+    assert_eq!(normalize(&[1e-200, 0.0])?, [1.0, 0.0]);
+    let normalized = normalize(&[1e200, 1e200])?;
+    assert!(normalized
+        .iter()
+        .all(|x| (x - std::f64::consts::FRAC_1_SQRT_2).abs() < 1e-12));
+
+    let data: [[f64; 2]; 2] = [[1.0, 0.0], [1e200, 1e200]];
+    let tree = CosineTree::new(&data, 1)?;
+    let result = tree.query_nearest_k(&[1.0, 1.0], 2)?;
+    assert_eq!(result.1, vec![1, 0]);
+    assert!((result.0[0] - 1.0).abs() < 1e-12);
+
+    Ok(())
+}
+
+fn cosine(a: &[f64; D], b: &[f64; D]) -> f64 {
+    let dot: f64 = (0..D).map(|i| a[i] * b[i]).sum();
+    let norm = |x: &[f64; D]| x.iter().map(|x| x * x).sum::<f64>().sqrt();
+    dot / (norm(a) * norm(b))
+}
+
+fn random_point(rng: &mut ThreadRng) -> [f64; D] {
+    let scale: f64 = rng.gen_range(0.1..10.0);
+    [(); D].map(|_| scale * rng.gen_range(-1.0..1.0))
+}
@@ -0,0 +1,114 @@
+use fnntw::Tree;
+use rand::{rngs::ThreadRng, Rng};
+use std::error::Error;
+
+const NDATA: usize = 1_000;
+const NQUERY: usize = 2_000;
+const BOXSIZE: [f64; 3] = [1.0; 3];
+const D: usize = 3;
+const KS: [usize; 3] = [1, 4, 16];
+const NDISTANCES: usize = 20;
+
+#[test]
+fn test_brute_force_knn_cdf() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random data, query, and distance grid
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+    let query: Vec<[f64; D]> = (0..NQUERY).map(|_| random_point(&mut rng)).collect();
+    let distances: Vec<f64> = (1..=NDISTANCES).map(|i| i as f64 * 0.01).collect();
+
+    // Construct tree
+    let tree = Tree::<'_, _, D>::new(&data, 8)?;
+
+    // Compute CDFs and brute force check results
+    let knn_cdf = tree.knn_cdf(&query, &KS, &distances)?;
+    let expected = brute_force_knn_cdf(&query, &data, &distances, None);
+    assert_eq!(knn_cdf.cdfs, expected);
+
+    // Generated queries should match
+    let generated = tree.knn_cdf_with(query.len(), |i| query[i], &KS, &distances)?;
+    assert_eq!(generated, knn_cdf);
+
+    // Peaked CDFs never exceed one half
+    for peaked in knn_cdf.peaked_cdfs() {
+        assert!(peaked.iter().all(|p| *p <= 0.5));
+    }
+
+    // Invalid ks and distances
+    assert!(tree.knn_cdf(&query, &[0], &distances).is_err());
+    assert!(tree.knn_cdf(&query, &[NDATA + 1], &distances).is_err());
+    assert!(tree.knn_cdf(&query, &KS, &[0.2, 0.1]).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_brute_force_periodic_knn_cdf() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random data, query, and distance grid
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+    let query: Vec<[f64; D]> = (0..NQUERY).map(|_| random_point(&mut rng)).collect();
+    let distances: Vec<f64> = (1..=NDISTANCES).map(|i| i as f64 * 0.01).collect();
+
+    // Construct tree
+    let tree = Tree::<'_, _, D>::new(&data, 8)?.with_boxsize(&BOXSIZE)?;
+
+    // Compute CDFs and brute force check results
+    let knn_cdf = tree.knn_cdf(&query, &KS, &distances)?;
+    let expected = brute_force_knn_cdf(&query, &data, &distances, Some(&BOXSIZE));
+    assert_eq!(knn_cdf.cdfs, expected);
+
+    Ok(())
+}
+
+fn random_point<const D: usize>(rng: &mut ThreadRng) -> [f64; D] {
+    [(); D].map(|_| rng.gen())
+}
+
+fn brute_force_knn_cdf<const D: usize>(
+    query: &[[f64; D]],
+    data: &[[f64; D]],
+    distances: &[f64],
+    boxsize: Option<&[f64; D]>,
+) -> Vec<Vec<f64>> {
+    let mut counts = vec![vec![0; distances.len()]; KS.len()];
+    for q in query {
+        let mut all: Vec<f64> = data
+            .iter()
+            .map(|d| {
+                let mut dist2 = 0.0;
+                for idx in 0..D {
+                    let mut dx = (q[idx] - d[idx]).abs();
+                    if let Some(boxsize) = boxsize {
+                        dx = dx.min(boxsize[idx] - dx);
+                    }
+                    dist2 += dx * dx;
+                }
+                dist2
+            })
+            .collect();
+        all.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        for (i, k) in KS.iter().enumerate() {
+            for (j, distance) in distances.iter().enumerate() {
+                if all[k - 1] <= distance * distance {
+                    counts[i][j] += 1;
+                }
+            }
+        }
+    }
+
+    counts
+        .into_iter()
+        .map(|counts| {
+            counts
+                .into_iter()
+                .map(|count| count as f64 / query.len() as f64)
+                .collect()
+        })
+        .collect()
+}
@@ -0,0 +1,125 @@
+use fnntw::mahalanobis::{Mahalanobis, MahalanobisTree};
+use rand::{rngs::ThreadRng, Rng};
+use std::error::Error;
+
+const NDATA: usize = 1_000;
+const NQUERY: usize = 500;
+const D: usize = 3;
+const K: usize = 8;
+const RADIUS: f64 = 0.3;
+const COVARIANCE: [[f64; D]; D] = [[2.0, 0.6, 0.3], [0.6, 1.0, -0.2], [0.3, -0.2, 0.5]];
+
+#[test]
+fn test_brute_force_mahalanobis() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random data, query
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+    let query: Vec<[f64; D]> = (0..NQUERY).map(|_| random_point(&mut rng)).collect();
+
+    // Check against brute force with the explicitly inverted covariance
+    let inverse = invert(COVARIANCE);
+    let tree = MahalanobisTree::new(&data, &COVARIANCE, 8)?;
+    let par_tree = MahalanobisTree::new_parallel(&data, &COVARIANCE, 8, 1)?;
+    for q in &query {
+        let mut expected: Vec<(f64, u64)> = data
+            .iter()
+            .zip(0..)
+            .map(|(d, index)| (mahalanobis(q, d, &inverse), index))
+            .collect();
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        // Nearest neighbor
+        let result = tree.query_nearest(q)?;
+        assert_eq!(result.1, expected[0].1);
+        assert!((result.0 - processed(expected[0].0)).abs() < 1e-12);
+        assert!(
+            (tree.mahalanobis().dist(q, &data[result.1 as usize]) - expected[0].0).abs() < 1e-12
+        );
+
+        // k nearest neighbors, with either build
+        let result = tree.query_nearest_k(q, K)?;
+        assert_eq!(result, par_tree.query_nearest_k(q, K)?);
+        for ((distance, index), (expected_distance, expected_index)) in
+            result.0.iter().zip(&result.1).zip(&expected)
+        {
+            assert_eq!(index, expected_index);
+            assert!((distance - processed(*expected_distance)).abs() < 1e-12);
+        }
+        #[cfg(not(feature = "no-position"))]
+        for (position, index) in result.2.iter().zip(&result.1) {
+            assert_eq!(position.map(|x| *x), data[*index as usize]);
+        }
+
+        // Neighbors within a radius
+        let within: Vec<u64> = expected
+            .iter()
+            .take_while(|(distance, _)| *distance <= RADIUS)
+            .map(|(_, index)| *index)
+            .collect();
+        let result = tree.query_ball_point(q, RADIUS)?;
+        assert_eq!(result.1, within);
+        assert_eq!(tree.count_within(q, RADIUS)?, within.len() as u64);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_invalid_covariance() {
+    // Not positive definite
+    assert!(Mahalanobis::new(&[[1.0, 2.0], [2.0, 1.0]]).is_err());
+    assert!(Mahalanobis::new(&[[1.0, 0.0], [0.0, 0.0]]).is_err());
+    // Not finite
+    assert!(Mahalanobis::new(&[[1.0, f64::NAN], [f64::NAN, 1.0]]).is_err());
+    assert!(Mahalanobis::new(&[[f64::INFINITY, 0.0], [0.0, 1.0]]).is_err());
+
+    let data = [[0.0, 0.0], [1.0, 1.0]];
+    assert!(MahalanobisTree::new(&data, &[[1.0, 2.0], [2.0, 1.0]], 1).is_err());
+}
+
+/// The distance returned by queries, which is the squared distance unless `sqrt-dist2` is
+/// enabled.
+fn processed(distance: f64) -> f64 {
+    #[cfg(feature = "sqrt-dist2")]
+    return distance;
+    #[cfg(not(feature = "sqrt-dist2"))]
+    return distance.powi(2);
+}
+
+fn mahalanobis(a: &[f64; D], b: &[f64; D], inverse: &[[f64; D]; D]) -> f64 {
+    let dx: Vec<f64> = (0..D).map(|i| a[i] - b[i]).collect();
+    (0..D)
+        .flat_map(|i| (0..D).map(move |j| (i, j)))
+        .map(|(i, j)| dx[i] * inverse[i][j] * dx[j])
+        .sum::<f64>()
+        .sqrt()
+}
+
+/// Gauss-Jordan elimination without pivoting, which is fine for the well conditioned covariance
+fn invert(mut matrix: [[f64; D]; D]) -> [[f64; D]; D] {
+    let mut inverse = [[0.0; D]; D];
+    for i in 0..D {
+        inverse[i][i] = 1.0;
+    }
+    for i in 0..D {
+        let pivot = matrix[i][i];
+        for j in 0..D {
+            matrix[i][j] /= pivot;
+            inverse[i][j] /= pivot;
+        }
+        for r in (0..D).filter(|r| *r != i) {
+            let factor = matrix[r][i];
+            for j in 0..D {
+                matrix[r][j] -= factor * matrix[i][j];
+                inverse[r][j] -= factor * inverse[i][j];
+            }
+        }
+    }
+    inverse
+}
+
+fn random_point(rng: &mut ThreadRng) -> [f64; D] {
+    [(); D].map(|_| rng.gen())
+}
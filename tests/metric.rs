@@ -0,0 +1,128 @@
+use fnntw::{metric::Metric, Tree};
+use rand::{rngs::ThreadRng, Rng};
+use std::error::Error;
+
+const NDATA: usize = 2_000;
+const NQUERY: usize = 500;
+const BOXSIZE: [f64; 3] = [1.0; 3];
+const D: usize = 3;
+const K: usize = 8;
+const RADIUS: f64 = 0.1;
+
+/// The taxicab metric, defined only by its distance along a single axis so that every other
+/// method of the trait uses its provided implementation.
+#[derive(Debug, Clone, Copy)]
+struct Taxicab;
+
+impl Metric<f64, D> for Taxicab {
+    fn axis_dist(&self, _axis: usize, dx: f64) -> f64 {
+        dx
+    }
+
+    fn to_distance(&self, reduced: f64) -> f64 {
+        reduced
+    }
+
+    fn to_reduced(&self, distance: f64) -> f64 {
+        distance
+    }
+}
+
+#[test]
+fn test_custom_metric() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random data, query
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+    let query: Vec<[f64; D]> = (0..NQUERY).map(|_| random_point(&mut rng)).collect();
+
+    // Check against brute force, with and without pbcs
+    let tree = Tree::<'_, _, D>::new(&data, 8)?.with_metric(Taxicab);
+    check_taxicab(&tree, &query, None)?;
+    let tree = tree.with_boxsize(&BOXSIZE)?;
+    check_taxicab(&tree, &query, Some(&BOXSIZE))?;
+
+    Ok(())
+}
+
+#[test]
+fn test_default_metric() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random data, query
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+    let query: Vec<[f64; D]> = (0..NQUERY).map(|_| random_point(&mut rng)).collect();
+
+    // Swapping the default metric for a copy of it does not change any result
+    let tree = Tree::<'_, _, D>::new(&data, 8)?.with_boxsize(&BOXSIZE)?;
+    let copy = Tree::<'_, _, D>::new(&data, 8)?
+        .with_boxsize(&BOXSIZE)?
+        .with_metric(*tree.metric());
+    for q in &query {
+        assert_eq!(tree.query_nearest(q)?, copy.query_nearest(q)?);
+        assert_eq!(tree.query_nearest_k(q, K)?, copy.query_nearest_k(q, K)?);
+    }
+
+    Ok(())
+}
+
+fn check_taxicab(
+    tree: &Tree<'_, f64, D, Taxicab>,
+    query: &[[f64; D]],
+    boxsize: Option<&[f64; D]>,
+) -> Result<(), Box<dyn Error>> {
+    let data = tree.get_data();
+
+    for q in query {
+        let mut expected: Vec<(f64, u64)> = data
+            .iter()
+            .enumerate()
+            .map(|(index, d)| (taxicab(q, d, boxsize), index as u64))
+            .collect();
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        // Nearest neighbor
+        let result = tree.query_nearest(q)?;
+        assert_eq!(result.1, expected[0].1);
+        assert!((result.0 - expected[0].0).abs() < 1e-12);
+
+        // k nearest neighbors
+        let result = tree.query_nearest_k(q, K)?;
+        for ((distance, index), (expected_distance, expected_index)) in
+            result.0.iter().zip(&result.1).zip(&expected[..K])
+        {
+            assert_eq!(index, expected_index);
+            assert!((distance - expected_distance).abs() < 1e-12);
+        }
+
+        // Neighbors within a radius
+        let within: Vec<(f64, u64)> = expected
+            .iter()
+            .copied()
+            .take_while(|(distance, _)| *distance <= RADIUS)
+            .collect();
+        let result = tree.query_ball_point(q, RADIUS)?;
+        assert_eq!(result.1, within.iter().map(|n| n.1).collect::<Vec<_>>());
+        assert_eq!(tree.count_within(q, RADIUS)?, within.len() as u64);
+    }
+
+    Ok(())
+}
+
+fn taxicab(a: &[f64; D], b: &[f64; D], boxsize: Option<&[f64; D]>) -> f64 {
+    (0..D)
+        .map(|i| {
+            let dx = (a[i] - b[i]).abs();
+            match boxsize {
+                Some(boxsize) => dx.min(boxsize[i] - dx),
+                None => dx,
+            }
+        })
+        .sum()
+}
+
+fn random_point(rng: &mut ThreadRng) -> [f64; D] {
+    [(); D].map(|_| rng.gen())
+}
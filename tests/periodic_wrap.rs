@@ -0,0 +1,122 @@
+use fnntw::Tree;
+use rand::{rngs::ThreadRng, Rng};
+use std::error::Error;
+
+const NDATA: usize = 1_000;
+const NQUERY: usize = 200;
+const D: usize = 3;
+const K: usize = 8;
+const RADIUS: f64 = 0.2;
+const LOWER: [f64; D] = [2.0, -3.0, 10.0];
+const UPPER: [f64; D] = [3.0, -2.5, 11.5];
+
+#[test]
+fn test_periodic_wrap() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random data, query within the box
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+    let query: Vec<[f64; D]> = (0..NQUERY).map(|_| random_point(&mut rng)).collect();
+
+    // Periodic along every axis, and open along the last axis
+    let bounds = [0, 1, 2].map(|i| Some((LOWER[i], UPPER[i])));
+    for bounds in [bounds, [bounds[0], bounds[1], None]] {
+        let tree = Tree::<'_, _, D>::new(&data, 8)?.with_periodic_bounds(&bounds)?;
+
+        for q in &query {
+            // Queries shifted by whole boxes along the periodic axes are wrapped into the box
+            let shifted = shift(&mut rng, q, &bounds);
+
+            let (expected, result) = (tree.query_nearest(q)?, tree.query_nearest(&shifted)?);
+            assert_eq!(result.1, expected.1);
+            assert!((result.0 - expected.0).abs() < 1e-12);
+
+            let (expected, result) = (
+                tree.query_nearest_k(q, K)?,
+                tree.query_nearest_k(&shifted, K)?,
+            );
+            assert_eq!(result.1, expected.1);
+            assert_close(&result.0, &expected.0);
+
+            let (expected, result) = (
+                tree.query_ball_point(q, RADIUS)?,
+                tree.query_ball_point(&shifted, RADIUS)?,
+            );
+            assert_eq!(result.1, expected.1);
+            assert_close(&result.0, &expected.0);
+
+            assert_eq!(
+                tree.count_within(&shifted, RADIUS)?,
+                tree.count_within(q, RADIUS)?
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg(all(feature = "parallel", feature = "no-position"))]
+fn test_periodic_wrap_par() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random data, query within the box
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+    let query: Vec<[f64; D]> = (0..NQUERY).map(|_| random_point(&mut rng)).collect();
+
+    // Periodic along every axis, and open along the last axis
+    let bounds = [0, 1, 2].map(|i| Some((LOWER[i], UPPER[i])));
+    for bounds in [bounds, [bounds[0], bounds[1], None]] {
+        let tree = Tree::<'_, _, D>::new_parallel(&data, 8, 1)?.with_periodic_bounds(&bounds)?;
+
+        // Queries shifted by whole boxes along the periodic axes are wrapped into the box
+        let shifted: Vec<[f64; D]> = query.iter().map(|q| shift(&mut rng, q, &bounds)).collect();
+
+        let (expected, result) = (
+            tree.query_nearest_k_parallel(&query, K)?,
+            tree.query_nearest_k_parallel(&shifted, K)?,
+        );
+        assert_eq!(result.1, expected.1);
+        assert_close(&result.0, &expected.0);
+
+        let (expected, result) = (
+            tree.query_nearest_k_parallel_with(&query, K)?,
+            tree.query_nearest_k_parallel_with(&shifted, K)?,
+        );
+        assert_eq!(result.1, expected.1);
+        assert_close(&result.0, &expected.0);
+
+        let (expected, result) = (
+            tree.query_nearest_k_parallel_axis(&query, K, 0)?,
+            tree.query_nearest_k_parallel_axis(&shifted, K, 0)?,
+        );
+        assert_close(&result.0, &expected.0);
+        assert_close(&result.1, &expected.1);
+    }
+
+    Ok(())
+}
+
+/// Shifts `point` by a random number of whole boxes along every periodic axis
+fn shift(rng: &mut ThreadRng, point: &[f64; D], bounds: &[Option<(f64, f64)>; D]) -> [f64; D] {
+    let mut shifted = *point;
+    for (component, bounds) in shifted.iter_mut().zip(bounds) {
+        if let Some((lower, upper)) = bounds {
+            *component += rng.gen_range(-3..=3) as f64 * (upper - lower);
+        }
+    }
+    shifted
+}
+
+fn assert_close(a: &[f64], b: &[f64]) {
+    assert_eq!(a.len(), b.len());
+    for (a, b) in a.iter().zip(b) {
+        assert!((a - b).abs() < 1e-12);
+    }
+}
+
+fn random_point(rng: &mut ThreadRng) -> [f64; D] {
+    [0, 1, 2].map(|i| rng.gen_range(LOWER[i]..UPPER[i]))
+}
@@ -0,0 +1,67 @@
+#![cfg(feature = "parallel")]
+
+use fnntw::Tree;
+
+type T = f64;
+const D: usize = 3;
+const QUERY: usize = 1_000;
+const BOXSIZE: [T; D] = [1.0; D];
+const NDATA: usize = 1_000;
+const RADIUS: T = 0.1;
+
+#[test]
+fn test_query_ball_point_parallel() {
+    let data: Vec<[T; D]> = (0..NDATA)
+        .map(|_| [(); D].map(|_| rand::random()))
+        .collect();
+    let query: Vec<[T; D]> = (0..QUERY)
+        .map(|_| [(); D].map(|_| rand::random()))
+        .collect();
+    let radii: Vec<T> = (0..QUERY).map(|_| rand::random::<T>() * 0.2).collect();
+
+    let tree = Tree::new(&data, 32).unwrap();
+
+    // non pbc check, with both a single radius and per-query radii
+    check_csr(&tree, &query, &[RADIUS; QUERY], |tree, query| {
+        tree.query_ball_point_parallel(query, RADIUS).unwrap()
+    });
+    check_csr(&tree, &query, &radii, |tree, query| {
+        tree.query_ball_point_parallel_radii(query, &radii).unwrap()
+    });
+
+    // pbc check
+    let tree = tree.with_boxsize(&BOXSIZE).unwrap();
+    check_csr(&tree, &query, &[RADIUS; QUERY], |tree, query| {
+        tree.query_ball_point_parallel(query, RADIUS).unwrap()
+    });
+    check_csr(&tree, &query, &radii, |tree, query| {
+        tree.query_ball_point_parallel_radii(query, &radii).unwrap()
+    });
+
+    // Mismatched radii
+    assert!(tree
+        .query_ball_point_parallel_radii(&query, &radii[1..])
+        .is_err());
+}
+
+fn check_csr(
+    tree: &Tree<'_, T, D>,
+    query: &[[T; D]],
+    radii: &[T],
+    par_query: impl Fn(&Tree<'_, T, D>, &[[T; D]]) -> fnntw::utils::QueryBallCsrResult<'static, T, D>,
+) {
+    let par_result = par_query(tree, query);
+    let offsets = &par_result.0;
+    assert_eq!(offsets.len(), query.len() + 1);
+    assert_eq!(*offsets.last().unwrap(), par_result.1.len());
+    assert_eq!(*offsets.last().unwrap(), par_result.2.len());
+
+    for (i, (q, r)) in query.iter().zip(radii).enumerate() {
+        let non_par_result = tree.query_ball_point(q, *r).unwrap();
+        let row = offsets[i]..offsets[i + 1];
+        assert_eq!(&non_par_result.0, &par_result.1[row.clone()], "{i}");
+        assert_eq!(&non_par_result.1, &par_result.2[row.clone()], "{i}");
+        #[cfg(not(feature = "no-position"))]
+        assert_eq!(&non_par_result.2, &par_result.3[row], "{i}");
+    }
+}
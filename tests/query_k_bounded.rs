@@ -0,0 +1,137 @@
+use fnntw::{utils::FnntwError, Tree};
+use rand::{rngs::ThreadRng, Rng};
+use std::error::Error;
+
+const NDATA: usize = 2_000;
+const NQUERY: usize = 500;
+const BOXSIZE: [f64; 3] = [1.0; 3];
+const D: usize = 3;
+const K: usize = 16;
+const MAX_RADIUS: f64 = 0.08;
+
+#[test]
+fn test_query_nearest_k_bounded() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random data, query
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+    let query: Vec<[f64; D]> = (0..NQUERY).map(|_| random_point(&mut rng)).collect();
+
+    // Check against brute force, with and without pbcs
+    let tree = Tree::<'_, _, D>::new(&data, 8)?;
+    check_bounded(&tree, &query, MAX_RADIUS, None)?;
+    let tree = tree.with_boxsize(&BOXSIZE)?;
+    check_bounded(&tree, &query, MAX_RADIUS, Some(&BOXSIZE))?;
+
+    // Invalid radius
+    assert!(tree.query_nearest_k_bounded(&query[0], K, -1.0).is_err());
+    assert!(tree
+        .query_nearest_k_bounded(&query[0], K, f64::INFINITY)
+        .is_err());
+
+    // Invalid k
+    assert!(matches!(
+        tree.query_nearest_k_bounded(&query[0], 0, MAX_RADIUS),
+        Err(FnntwError::InvalidK)
+    ));
+    #[cfg(feature = "parallel")]
+    assert!(matches!(
+        tree.query_nearest_k_parallel_bounded(&query, 0, MAX_RADIUS),
+        Err(FnntwError::InvalidK)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_query_nearest_k_bounded_lattice() -> Result<(), Box<dyn Error>> {
+    // Integer lattice, on which many neighbors lie exactly on the bound
+    let data: Vec<[f64; D]> = (0..8 * 8 * 8)
+        .map(|i| [(i % 8) as f64, ((i / 8) % 8) as f64, (i / 64) as f64])
+        .collect();
+
+    let tree = Tree::<'_, _, D>::new(&data, 4)?;
+    check_bounded(&tree, &data, 1.0, None)?;
+    check_bounded(&tree, &data, 0.0, None)?;
+    let tree = tree.with_boxsize(&[8.0; D])?;
+    check_bounded(&tree, &data, 1.0, Some(&[8.0; D]))?;
+
+    Ok(())
+}
+
+fn check_bounded(
+    tree: &Tree<'_, f64, D>,
+    query: &[[f64; D]],
+    max_radius: f64,
+    boxsize: Option<&[f64; D]>,
+) -> Result<(), Box<dyn Error>> {
+    let data = tree.get_data();
+
+    #[cfg(feature = "parallel")]
+    let par_result = tree.query_nearest_k_parallel_bounded(query, K, max_radius)?;
+
+    for (i, q) in query.iter().enumerate() {
+        let result = tree.query_nearest_k_bounded(q, K, max_radius)?;
+
+        let expected = brute_force_distances(q, data, max_radius, boxsize);
+        #[cfg(feature = "sqrt-dist2")]
+        let expected: Vec<f64> = expected.into_iter().map(f64::sqrt).collect();
+        assert_eq!(result.0.len(), expected.len());
+        assert_eq!(result.1.len(), expected.len());
+        for (r, e) in result.0.iter().zip(&expected) {
+            assert!((r - e).abs() < 1e-12, "{r} != {e}");
+        }
+
+        #[cfg(feature = "parallel")]
+        {
+            // Found neighbors, followed by sentinels
+            let row = i * K..(i + 1) * K;
+            let found = result.0.len();
+            assert_eq!(result.0, &par_result.0[row.start..row.start + found]);
+            assert_eq!(result.1, &par_result.1[row.start..row.start + found]);
+            #[cfg(not(feature = "no-position"))]
+            assert_eq!(result.2, &par_result.2[row.start..row.start + found]);
+            assert!(par_result.0[row.start + found..row.end]
+                .iter()
+                .all(|d| d.is_infinite()));
+            assert!(par_result.1[row.start + found..row.end]
+                .iter()
+                .all(|idx| *idx == u64::MAX));
+        }
+        #[cfg(not(feature = "parallel"))]
+        let _ = i;
+    }
+
+    Ok(())
+}
+
+fn random_point<const D: usize>(rng: &mut ThreadRng) -> [f64; D] {
+    [(); D].map(|_| rng.gen())
+}
+
+fn brute_force_distances(
+    q: &[f64; D],
+    data: &[[f64; D]],
+    max_radius: f64,
+    boxsize: Option<&[f64; D]>,
+) -> Vec<f64> {
+    let mut all: Vec<f64> = data
+        .iter()
+        .map(|d| {
+            let mut dist2 = 0.0;
+            for idx in 0..D {
+                let mut dx = (q[idx] - d[idx]).abs();
+                if let Some(boxsize) = boxsize {
+                    dx = dx.min(boxsize[idx] - dx);
+                }
+                dist2 += dx * dx;
+            }
+            dist2
+        })
+        .filter(|dist2| *dist2 <= max_radius * max_radius)
+        .collect();
+    all.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    all.truncate(K);
+    all
+}
@@ -0,0 +1,103 @@
+use fnntw::Tree;
+use rand::{rngs::ThreadRng, Rng};
+use std::error::Error;
+
+const NDATA: usize = 1_000;
+const NDUPLICATES: usize = 10;
+const BOXSIZE: [f64; 3] = [1.0; 3];
+const D: usize = 3;
+const K: usize = 16;
+
+#[test]
+fn test_query_nearest_k_exclude_self() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random data, where the last few points duplicate the first few
+    let mut data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+    for i in 0..NDUPLICATES {
+        data[NDATA - 1 - i] = data[i];
+    }
+
+    // Check against brute force, with and without pbcs
+    let tree = Tree::<'_, _, D>::new(&data, 8)?;
+    check_exclude_self(&tree, None)?;
+    let tree = tree.with_boxsize(&BOXSIZE)?;
+    check_exclude_self(&tree, Some(&BOXSIZE))?;
+
+    // Invalid index
+    assert!(tree.query_nearest_k_exclude(&data[0], K, NDATA).is_err());
+
+    Ok(())
+}
+
+fn check_exclude_self(
+    tree: &Tree<'_, f64, D>,
+    boxsize: Option<&[f64; D]>,
+) -> Result<(), Box<dyn Error>> {
+    let data = tree.get_data();
+
+    #[cfg(feature = "parallel")]
+    let par_result = tree.query_nearest_k_parallel_exclude_self(data, K)?;
+
+    for (i, q) in data.iter().enumerate() {
+        let result = tree.query_nearest_k_exclude(q, K, i)?;
+
+        // The query itself is never a neighbor, but its duplicate is the nearest
+        assert!(!result.1.contains(&(i as u64)));
+        if i < NDUPLICATES {
+            assert_eq!(result.0[0], 0.0);
+            assert_eq!(result.1[0], (NDATA - 1 - i) as u64);
+        }
+
+        let expected = brute_force_distances(q, data, i, boxsize);
+        #[cfg(feature = "sqrt-dist2")]
+        let expected: Vec<f64> = expected.into_iter().map(f64::sqrt).collect();
+        assert_eq!(result.0.len(), expected.len());
+        for (r, e) in result.0.iter().zip(&expected) {
+            assert!((r - e).abs() < 1e-12, "{r} != {e}");
+        }
+
+        #[cfg(feature = "parallel")]
+        {
+            let row = i * K..(i + 1) * K;
+            assert_eq!(result.0, &par_result.0[row.clone()]);
+            assert_eq!(result.1, &par_result.1[row.clone()]);
+            #[cfg(not(feature = "no-position"))]
+            assert_eq!(result.2, &par_result.2[row]);
+        }
+    }
+
+    Ok(())
+}
+
+fn random_point<const D: usize>(rng: &mut ThreadRng) -> [f64; D] {
+    [(); D].map(|_| rng.gen())
+}
+
+fn brute_force_distances(
+    q: &[f64; D],
+    data: &[[f64; D]],
+    exclude: usize,
+    boxsize: Option<&[f64; D]>,
+) -> Vec<f64> {
+    let mut all: Vec<f64> = data
+        .iter()
+        .enumerate()
+        .filter(|(j, _)| *j != exclude)
+        .map(|(_, d)| {
+            let mut dist2 = 0.0;
+            for idx in 0..D {
+                let mut dx = (q[idx] - d[idx]).abs();
+                if let Some(boxsize) = boxsize {
+                    dx = dx.min(boxsize[idx] - dx);
+                }
+                dist2 += dx * dx;
+            }
+            dist2
+        })
+        .collect();
+    all.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    all.truncate(K);
+    all
+}
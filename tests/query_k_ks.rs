@@ -0,0 +1,68 @@
+#![cfg(feature = "parallel")]
+
+use fnntw::Tree;
+use rand::{rngs::ThreadRng, Rng};
+use std::error::Error;
+
+const NDATA: usize = 1_000;
+const NQUERY: usize = 1_000;
+const BOXSIZE: [f64; 3] = [1.0; 3];
+const D: usize = 3;
+const MAX_K: usize = 32;
+
+#[test]
+fn test_query_nearest_k_parallel_ks() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random data, query, and k for every query (including zero and more than NDATA)
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+    let query: Vec<[f64; D]> = (0..NQUERY).map(|_| random_point(&mut rng)).collect();
+    let mut ks: Vec<usize> = (0..NQUERY).map(|_| rng.gen_range(0..=MAX_K)).collect();
+    ks[0] = 0;
+    ks[1] = NDATA + 1;
+
+    // Check against single queries, with and without pbcs
+    let tree = Tree::<'_, _, D>::new(&data, 8)?;
+    check_ks(&tree, &query, &ks)?;
+    let tree = tree.with_boxsize(&BOXSIZE)?;
+    check_ks(&tree, &query, &ks)?;
+
+    // Mismatched lengths
+    assert!(tree.query_nearest_k_parallel_ks(&query, &ks[1..]).is_err());
+
+    Ok(())
+}
+
+fn check_ks(
+    tree: &Tree<'_, f64, D>,
+    query: &[[f64; D]],
+    ks: &[usize],
+) -> Result<(), Box<dyn Error>> {
+    let result = tree.query_nearest_k_parallel_ks(query, ks)?;
+    let offsets = &result.0;
+    assert_eq!(offsets.len(), query.len() + 1);
+    assert_eq!(offsets[0], 0);
+    assert_eq!(*offsets.last().unwrap(), result.1.len());
+    assert_eq!(result.1.len(), result.2.len());
+
+    for (i, (q, k)) in query.iter().zip(ks).enumerate() {
+        let row = offsets[i]..offsets[i + 1];
+        assert_eq!(row.len(), (*k).min(NDATA));
+        if *k == 0 {
+            continue;
+        }
+
+        let expected = tree.query_nearest_k(q, *k)?;
+        assert_eq!(expected.0, &result.1[row.clone()]);
+        assert_eq!(expected.1, &result.2[row.clone()]);
+        #[cfg(not(feature = "no-position"))]
+        assert_eq!(expected.2, &result.3[row]);
+    }
+
+    Ok(())
+}
+
+fn random_point<const D: usize>(rng: &mut ThreadRng) -> [f64; D] {
+    [(); D].map(|_| rng.gen())
+}
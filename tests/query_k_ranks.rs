@@ -0,0 +1,67 @@
+use fnntw::Tree;
+use rand::{rngs::ThreadRng, Rng};
+use std::error::Error;
+
+const NDATA: usize = 1_000;
+const NQUERY: usize = 1_000;
+const BOXSIZE: [f64; 3] = [1.0; 3];
+const D: usize = 3;
+const RANKS: [usize; 4] = [1, 2, 8, 32];
+
+#[test]
+fn test_query_nearest_k_ranks() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random data, query
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+    let query: Vec<[f64; D]> = (0..NQUERY).map(|_| random_point(&mut rng)).collect();
+
+    // Check against the full k query, with and without pbcs
+    let tree = Tree::<'_, _, D>::new(&data, 8)?;
+    check_ranks(&tree, &query)?;
+    let tree = tree.with_boxsize(&BOXSIZE)?;
+    check_ranks(&tree, &query)?;
+
+    // Invalid ranks
+    assert!(tree.query_nearest_k_ranks(&query[0], &[]).is_err());
+    assert!(tree.query_nearest_k_ranks(&query[0], &[0, 1]).is_err());
+    assert!(tree.query_nearest_k_ranks(&query[0], &[2, 1]).is_err());
+    assert!(tree.query_nearest_k_ranks(&query[0], &[NDATA + 1]).is_err());
+
+    Ok(())
+}
+
+fn check_ranks(tree: &Tree<'_, f64, D>, query: &[[f64; D]]) -> Result<(), Box<dyn Error>> {
+    let k = *RANKS.last().unwrap();
+
+    #[cfg(feature = "parallel")]
+    let par_result = tree.query_nearest_k_ranks_parallel(query, &RANKS)?;
+
+    for (i, q) in query.iter().enumerate() {
+        let result = tree.query_nearest_k_ranks(q, &RANKS)?;
+        let full = tree.query_nearest_k(q, k)?;
+
+        assert_eq!(result.0, RANKS.map(|rank| full.0[rank - 1]));
+        assert_eq!(result.1, RANKS.map(|rank| full.1[rank - 1]));
+        #[cfg(not(feature = "no-position"))]
+        assert_eq!(result.2, RANKS.map(|rank| full.2[rank - 1]));
+
+        #[cfg(feature = "parallel")]
+        {
+            let row = i * RANKS.len()..(i + 1) * RANKS.len();
+            assert_eq!(result.0, &par_result.0[row.clone()]);
+            assert_eq!(result.1, &par_result.1[row.clone()]);
+            #[cfg(not(feature = "no-position"))]
+            assert_eq!(result.2, &par_result.2[row]);
+        }
+        #[cfg(not(feature = "parallel"))]
+        let _ = i;
+    }
+
+    Ok(())
+}
+
+fn random_point<const D: usize>(rng: &mut ThreadRng) -> [f64; D] {
+    [(); D].map(|_| rng.gen())
+}
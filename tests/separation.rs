@@ -0,0 +1,109 @@
+use fnntw::{utils::FnntwError, Tree};
+use rand::{rngs::ThreadRng, Rng};
+use std::error::Error;
+
+const NDATA: usize = 2_000;
+const NQUERY: usize = 200;
+const D: usize = 3;
+const K: usize = 8;
+const RADIUS: f64 = 0.2;
+const LOWER: [f64; D] = [2.0, -3.0, 10.0];
+const UPPER: [f64; D] = [3.0, -2.5, 11.5];
+
+#[test]
+fn test_separation() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random data, query within the box
+    let data: Vec<[f64; D]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+    let query: Vec<[f64; D]> = (0..NQUERY).map(|_| random_point(&mut rng)).collect();
+
+    // Nonperiodic, periodic along every axis, and open along the last axis
+    let periodic = [0, 1, 2].map(|i| Some((LOWER[i], UPPER[i])));
+    for bounds in [[None; D], periodic, [periodic[0], periodic[1], None]] {
+        let tree = Tree::<'_, _, D>::new(&data, 8)?.with_periodic_bounds(&bounds)?;
+
+        for q in &query {
+            // k nearest neighbors
+            let (distances, indices, separations) = tree.query_nearest_k_separation(q, K)?;
+            assert_eq!(indices, tree.query_nearest_k(q, K)?.1);
+            check_separations(q, &data, &bounds, &distances, &indices, &separations);
+
+            // Neighbors within a radius
+            let (distances, indices, separations) = tree.query_ball_point_separation(q, RADIUS)?;
+            assert_eq!(indices, tree.query_ball_point(q, RADIUS)?.1);
+            check_separations(q, &data, &bounds, &distances, &indices, &separations);
+
+            // Queries shifted by whole boxes along the periodic axes are wrapped into the box
+            let shifted: [f64; D] =
+                std::array::from_fn(|i| q[i] + bounds[i].map_or(0.0, |(l, u)| 2.0 * (u - l)));
+            let result = tree.query_nearest_k_separation(&shifted, K)?;
+            assert_eq!(result.1, tree.query_nearest_k(q, K)?.1);
+            check_separations(q, &data, &bounds, &result.0, &result.1, &result.2);
+        }
+    }
+
+    // k must be nonzero
+    let tree = Tree::<'_, _, D>::new(&data, 8)?;
+    assert!(matches!(
+        tree.query_nearest_k_separation(&query[0], 0),
+        Err(FnntwError::InvalidK)
+    ));
+
+    Ok(())
+}
+
+/// Checks the separations against the minimum image separations, and that their norms are
+/// the distances returned
+fn check_separations(
+    query: &[f64; D],
+    data: &[[f64; D]],
+    bounds: &[Option<(f64, f64)>; D],
+    distances: &[f64],
+    indices: &[u64],
+    separations: &[[f64; D]],
+) {
+    assert_eq!(separations.len(), indices.len());
+    for ((distance, index), separation) in distances.iter().zip(indices).zip(separations) {
+        let expected = separation_brute_force(query, &data[*index as usize], bounds);
+        assert!((0..D).all(|i| (separation[i] - expected[i]).abs() < 1e-12));
+
+        let norm = separation.iter().map(|x| x.powi(2)).sum::<f64>().sqrt();
+        assert!((distance - processed(norm)).abs() < 1e-12);
+    }
+}
+
+/// The distance returned by queries, which is the squared distance unless `sqrt-dist2` is
+/// enabled.
+fn processed(distance: f64) -> f64 {
+    #[cfg(feature = "sqrt-dist2")]
+    return distance;
+    #[cfg(not(feature = "sqrt-dist2"))]
+    return distance.powi(2);
+}
+
+/// Shortest separation from `a` to the images of `b` along the periodic axes
+fn separation_brute_force(
+    a: &[f64; D],
+    b: &[f64; D],
+    bounds: &[Option<(f64, f64)>; D],
+) -> [f64; D] {
+    let mut separation = [0.0; D];
+    for i in 0..D {
+        separation[i] = b[i] - a[i];
+        if let Some((lower, upper)) = bounds[i] {
+            let side = upper - lower;
+            for shift in [-side, side] {
+                if (b[i] + shift - a[i]).abs() < separation[i].abs() {
+                    separation[i] = b[i] + shift - a[i];
+                }
+            }
+        }
+    }
+    separation
+}
+
+fn random_point(rng: &mut ThreadRng) -> [f64; D] {
+    [0, 1, 2].map(|i| rng.gen_range(LOWER[i]..UPPER[i]))
+}
@@ -0,0 +1,117 @@
+use fnntw::spherical::{AngleUnit, SphericalTree};
+use rand::{rngs::ThreadRng, Rng};
+use std::error::Error;
+
+const NDATA: usize = 2_000;
+const NQUERY: usize = 500;
+const K: usize = 8;
+// In degrees
+const RADIUS: f64 = 5.0;
+
+#[test]
+fn test_brute_force_spherical() -> Result<(), Box<dyn Error>> {
+    // Random number generator
+    let mut rng = rand::thread_rng();
+
+    // Generate random (ra, dec) in degrees, uniform on the sphere
+    let data: Vec<[f64; 2]> = (0..NDATA).map(|_| random_point(&mut rng)).collect();
+    let query: Vec<[f64; 2]> = (0..NQUERY).map(|_| random_point(&mut rng)).collect();
+
+    // Check against brute force in degrees, and in radians
+    let tree = SphericalTree::new(&data, AngleUnit::Degrees, 8)?;
+    check_brute_force(&tree, &query, RADIUS)?;
+    let data: Vec<[f64; 2]> = data.iter().map(|p| p.map(f64::to_radians)).collect();
+    let query: Vec<[f64; 2]> = query.iter().map(|p| p.map(f64::to_radians)).collect();
+    let tree = SphericalTree::new_parallel(&data, AngleUnit::Radians, 8, 1)?;
+    check_brute_force(&tree, &query, RADIUS.to_radians())?;
+
+    Ok(())
+}
+
+#[test]
+fn test_spherical_edge_cases() -> Result<(), Box<dyn Error>> {
+    let data: [[f64; 2]; 4] = [[0.0, 90.0], [180.0, -90.0], [10.0, 0.0], [370.0, 0.0]];
+    let tree = SphericalTree::new(&data, AngleUnit::Degrees, 1)?;
+
+    // Right ascension wraps around, and every right ascension at the poles is the same point
+    let (separations, indices) = tree.query_nearest_k(&[5.0, 0.0], 2)?;
+    assert_eq!(separations.len(), 2);
+    assert!(separations.iter().all(|s| (s - 5.0).abs() < 1e-9));
+    assert!(indices.contains(&2) && indices.contains(&3));
+    let (separation, index) = tree.query_nearest(&[123.0, 89.0])?;
+    assert_eq!(index, 0);
+    assert!((separation - 1.0).abs() < 1e-9);
+
+    // Radii beyond 180 degrees contain the whole sphere
+    assert_eq!(tree.count_within(&[0.0, 0.0], 180.0)?, 4);
+    assert_eq!(tree.count_within(&[0.0, 0.0], 720.0)?, 4);
+
+    // Invalid coordinates and radii
+    assert!(SphericalTree::new(&[[0.0, 90.5]], AngleUnit::Degrees, 1).is_err());
+    assert!(SphericalTree::new(&[[0.0, 1.6]], AngleUnit::Radians, 1).is_err());
+    assert!(SphericalTree::new(&[[f64::NAN, 0.0]], AngleUnit::Radians, 1).is_err());
+    assert!(tree.query_nearest(&[0.0, -91.0]).is_err());
+    assert!(tree.count_within(&[0.0, 0.0], -1.0).is_err());
+
+    Ok(())
+}
+
+fn check_brute_force(
+    tree: &SphericalTree<'_, f64>,
+    query: &[[f64; 2]],
+    radius: f64,
+) -> Result<(), Box<dyn Error>> {
+    let unit = tree.unit();
+    for q in query {
+        let mut expected: Vec<(f64, u64)> = tree
+            .get_data()
+            .iter()
+            .zip(0..)
+            .map(|(d, index)| (separation(q, d, unit), index))
+            .collect();
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        // Nearest neighbor
+        let result = tree.query_nearest(q)?;
+        assert_eq!(result.1, expected[0].1);
+        assert!((result.0 - expected[0].0).abs() < 1e-9);
+
+        // k nearest neighbors
+        let result = tree.query_nearest_k(q, K)?;
+        for ((distance, index), (expected_distance, expected_index)) in
+            result.0.iter().zip(&result.1).zip(&expected)
+        {
+            assert_eq!(index, expected_index);
+            assert!((distance - expected_distance).abs() < 1e-9);
+        }
+
+        // Neighbors within a radius
+        let within: Vec<u64> = expected
+            .iter()
+            .take_while(|(distance, _)| *distance <= radius)
+            .map(|(_, index)| *index)
+            .collect();
+        let result = tree.query_ball_point(q, radius)?;
+        assert_eq!(result.1, within);
+        assert_eq!(tree.count_within(q, radius)?, within.len() as u64);
+    }
+
+    Ok(())
+}
+
+/// Angular separation from the dot product of the unit vectors
+fn separation(a: &[f64; 2], b: &[f64; 2], unit: AngleUnit) -> f64 {
+    let [a, b] = [a, b].map(|[ra, dec]| {
+        let (ra, dec) = (unit.to_radians(*ra), unit.to_radians(*dec));
+        [dec.cos() * ra.cos(), dec.cos() * ra.sin(), dec.sin()]
+    });
+    let dot: f64 = (0..3).map(|i| a[i] * b[i]).sum();
+    unit.from_radians(dot.clamp(-1.0, 1.0).acos())
+}
+
+fn random_point(rng: &mut ThreadRng) -> [f64; 2] {
+    [
+        rng.gen_range(0.0..360.0),
+        rng.gen_range(-1.0f64..1.0).asin().to_degrees(),
+    ]
+}